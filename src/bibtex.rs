@@ -0,0 +1,700 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use readinglist::{Error, Format, ReadingEntry, Result, Status};
+use readinglist::fields::FieldDefinition;
+
+use crate::import::applyDefaults;
+use crate::output::{Value, toJson};
+
+/// An entry of a .bib file. Values are kept as written between the braces,
+/// with macros expanded and whitespace collapsed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BibEntry {
+    pub kind: String,
+    pub key: String,
+    pub fields: Vec<(String, String)>
+}
+
+/// The field keeping the entry as imported, so nothing is lost on export
+pub const BIBTEX_FIELD: &str = "bibtex";
+
+/// Fields naming where a paper appeared, in the order they are looked for
+const VENUE_FIELDS: [&str; 4] = ["journal", "booktitle", "publisher", "howpublished"];
+
+const MONTHS: [(&str, &str); 12] = [
+    ("jan", "January"), ("feb", "February"), ("mar", "March"), ("apr", "April"), ("may", "May"), ("jun", "June"),
+    ("jul", "July"), ("aug", "August"), ("sep", "September"), ("oct", "October"), ("nov", "November"), ("dec", "December")
+];
+
+/// Letters with TeX accents, e.g. `\"o`, by accent
+const ACCENTS: [(&str, &str, &str); 8] = [
+    ("\"", "aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+    ("'", "aeiouycnszACEIOUYNSZ", "áéíóúýćńśźÁĆÉÍÓÚÝŃŚŹ"),
+    ("`", "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ("^", "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ("~", "anoANO", "ãñõÃÑÕ"),
+    ("c", "cCsS", "çÇşŞ"),
+    ("v", "cszCSZ", "čšžČŠŽ"),
+    ("=", "aeiouAEIOU", "āēīōūĀĒĪŌŪ")
+];
+
+/// Letters and logos written as TeX commands, e.g. `\ss`
+const LETTERS: [(&str, &str); 13] = [
+    ("ss", "ß"), ("o", "ø"), ("O", "Ø"), ("aa", "å"), ("AA", "Å"), ("ae", "æ"), ("AE", "Æ"), ("l", "ł"), ("L", "Ł"), ("i", "ı"),
+    ("TeX", "TeX"), ("LaTeX", "LaTeX"), ("BibTeX", "BibTeX")
+];
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    macros: BTreeMap<String, String>
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.pos).cloned();
+    }
+
+    fn error(&self, reason: &str) -> Error {
+        let line = self.chars[..self.pos.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1;
+        return Error::Validation(format!("Line {}: {}", line, reason));
+    }
+
+    fn skipWhitespace(&mut self) {
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skipWhitespace();
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("Expected '{}'", c)));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    /// Names of entry types, fields and macros
+    fn identifier(&mut self) -> String {
+        self.skipWhitespace();
+        let start = self.pos;
+        while self.peek().map(|c| c.is_alphanumeric() || "-_:.+/".contains(c)).unwrap_or(false) {
+            self.pos += 1;
+        }
+        return self.chars[start..self.pos].iter().collect();
+    }
+
+    /// The text up to the closing delimiter of the group starting at `pos`.
+    /// Escaped braces don't count.
+    fn delimited(&mut self, close: char) -> Result<String> {
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => self.pos += 1,
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                _ if c == close && depth == 0 => {
+                    let text = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(text);
+                },
+                '}' => return Err(self.error("Unbalanced braces")),
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        return Err(self.error("An entry or value isn't closed before the end of the file"));
+    }
+
+    /// A value like `{The {TeX}book}`, `"Knuth"`, `1984` or `jan # " 1"`
+    fn value(&mut self) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            self.skipWhitespace();
+            match self.peek() {
+                Some('{') => { self.pos += 1; value.push_str(&self.delimited('}')?); },
+                Some('"') => { self.pos += 1; value.push_str(&self.delimited('"')?); },
+                Some(c) if c.is_ascii_digit() => value.push_str(&self.identifier()),
+                Some(c) if c.is_alphabetic() => {
+                    let name = self.identifier().to_lowercase();
+                    match self.macros.get(&name).map(|x| x.as_str()).or_else(|| MONTHS.iter().find(|(m, _)| *m == name).map(|(_, x)| *x)) {
+                        Some(x) => value.push_str(x),
+                        None => return Err(self.error(&format!("Unknown string '{}'", name)))
+                    }
+                },
+                _ => return Err(self.error("Expected a value"))
+            }
+
+            self.skipWhitespace();
+            if self.peek() != Some('#') {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        return Ok(value.split_whitespace().collect::<Vec<&str>>().join(" "));
+    }
+
+    /// `name = value` pairs separated by commas up to `close`
+    fn fields(&mut self, close: char) -> Result<Vec<(String, String)>> {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        loop {
+            self.skipWhitespace();
+            match self.peek() {
+                Some(',') => { self.pos += 1; continue; },
+                Some(c) if c == close => { self.pos += 1; return Ok(fields); },
+                None => return Err(self.error("An entry isn't closed before the end of the file")),
+                _ => {}
+            }
+
+            let name = self.identifier().to_lowercase();
+            if name.is_empty() {
+                return Err(self.error("Expected the name of a field"));
+            }
+            self.expect('=')?;
+            let value = self.value()?;
+            fields.push((name, value));
+        }
+    }
+}
+
+/// Reads the entries of a .bib file. `@string` macros are expanded,
+/// `@comment` and `@preamble` left out.
+pub fn parseBibtex(text: &str) -> Result<Vec<BibEntry>> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, macros: BTreeMap::new() };
+    let mut entries: Vec<BibEntry> = Vec::new();
+    loop {
+        // anything outside entries is a comment
+        while parser.peek().map(|c| c != '@').unwrap_or(false) {
+            parser.pos += 1;
+        }
+        if parser.peek().is_none() {
+            return Ok(entries);
+        }
+        parser.pos += 1;
+
+        let kind = parser.identifier().to_lowercase();
+        parser.skipWhitespace();
+        let close = match parser.peek() {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => return Err(parser.error(&format!("Expected '{{' after @{}", kind)))
+        };
+        parser.pos += 1;
+
+        match kind.as_str() {
+            "comment" | "preamble" => { parser.delimited(close)?; },
+            "string" => {
+                for (name, value) in parser.fields(close)? {
+                    parser.macros.insert(name, value);
+                }
+            },
+            _ => {
+                parser.skipWhitespace();
+                let start = parser.pos;
+                while parser.peek().map(|c| c != ',' && c != close && !c.is_whitespace()).unwrap_or(false) {
+                    parser.pos += 1;
+                }
+                let key: String = parser.chars[start..parser.pos].iter().collect();
+                let fields = parser.fields(close)?;
+                entries.push(BibEntry { kind: kind, key: key, fields: fields });
+            }
+        }
+    }
+}
+
+/// Writes the entry on several lines, or on one to store it in a field
+fn writeEntry(entry: &BibEntry, multiline: bool) -> String {
+    let fields: Vec<String> = entry.fields.iter().map(|(name, value)| format!("{} = {{{}}}", name, value)).collect();
+    if multiline {
+        return format!("@{}{{{},\n{}}}\n", entry.kind, entry.key, fields.iter().map(|f| format!("  {},\n", f)).collect::<String>());
+    }
+    return format!("@{}{{{}, {}}}", entry.kind, entry.key, fields.join(", "));
+}
+
+/// The text of a value without braces and TeX, e.g. `Schr{\"o}dinger` is `Schrödinger`
+pub fn plainText(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut plain = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '{' | '}' => {},
+            '~' => plain.push(' '),
+            '\\' if i < chars.len() && "&%$#_{}\\".contains(chars[i]) => {
+                plain.push(chars[i]);
+                i += 1;
+            },
+            '\\' => {
+                let start = i;
+                let command: String = if i < chars.len() && !chars[i].is_alphabetic() {
+                    i += 1;
+                    chars[start..i].iter().collect()
+                } else {
+                    while i < chars.len() && chars[i].is_alphabetic() {
+                        i += 1;
+                    }
+                    chars[start..i].iter().collect()
+                };
+
+                if let Some((_, letters, accented)) = ACCENTS.iter().find(|(a, _, _)| *a == command) {
+                    // the letter may be braced or, after a command, follow a space
+                    while i < chars.len() && (chars[i] == '{' || chars[i] == ' ') {
+                        i += 1;
+                    }
+                    if i < chars.len() {
+                        let letter = chars[i];
+                        i += 1;
+                        match letters.chars().position(|x| x == letter) {
+                            Some(p) => plain.push(accented.chars().nth(p).unwrap()),
+                            None => plain.push(letter)
+                        }
+                        if i < chars.len() && chars[i] == '}' {
+                            i += 1;
+                        }
+                    }
+                } else if let Some((_, letter)) = LETTERS.iter().find(|(l, _)| *l == command) {
+                    plain.push_str(letter);
+                    if i < chars.len() && chars[i] == ' ' {
+                        i += 1;
+                    }
+                } else if i < chars.len() && chars[i] == ' ' && command.chars().all(|x| x.is_alphabetic()) {
+                    // commands like \emph just style their argument
+                    i += 1;
+                }
+            },
+            _ => plain.push(c)
+        }
+    }
+
+    return plain.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+/// Writes text as value of a field, see `plainText`
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "&%$#_{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    return escaped;
+}
+
+/// Splits at the `and`s between names that aren't braced
+fn splitNames(authors: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut name = String::new();
+    let mut depth = 0;
+    let words: Vec<&str> = authors.split(' ').collect();
+    for word in words {
+        if depth == 0 && word.eq_ignore_ascii_case("and") && !name.is_empty() {
+            names.push(std::mem::take(&mut name));
+            continue;
+        }
+        depth += word.matches('{').count() as i64 - word.matches('}').count() as i64;
+        if !name.is_empty() {
+            name.push(' ');
+        }
+        name.push_str(word);
+    }
+    if !name.is_empty() {
+        names.push(name);
+    }
+    return names;
+}
+
+/// Splits a name at its commas that aren't braced, e.g. `Steele, Jr., Guy`
+fn nameParts(name: &str) -> Vec<String> {
+    let mut parts: Vec<String> = vec![String::new()];
+    let mut depth = 0;
+    for c in name.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => { parts.push(String::new()); continue; },
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    return parts.iter().map(|x| x.trim().to_string()).collect();
+}
+
+/// The family and given names, or a literal one like `{Barnes and Noble}`
+fn splitName(name: &str) -> (String, String) {
+    let parts = nameParts(name);
+    match parts.len() {
+        1 => {
+            let plain = plainText(name);
+            if name.starts_with('{') && name.ends_with('}') {
+                return (plain, String::new());
+            }
+            match plain.rfind(' ') {
+                Some(p) => return (plain[p + 1..].to_string(), plain[..p].to_string()),
+                None => return (plain, String::new())
+            }
+        },
+        2 => return (plainText(&parts[0]), plainText(&parts[1])),
+        _ => return (format!("{} {}", plainText(&parts[0]), plainText(&parts[1])), plainText(&parts[2]))
+    }
+}
+
+/// Authors the way the list shows them, e.g. `Knuth, Donald E. and others`
+/// is `Donald E. Knuth, et al.`
+pub fn displayAuthors(authors: &str) -> String {
+    return splitNames(authors).iter()
+        .map(|name| {
+            if name == "others" {
+                return String::from("et al.");
+            }
+            let (family, given) = splitName(name);
+            return vec![given, family].into_iter().filter(|x| !x.is_empty()).collect::<Vec<String>>().join(" ");
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+}
+
+/// The inverse of `displayAuthors`
+fn bibtexAuthors(authors: &str) -> String {
+    return authors.split(", ")
+        .map(|name| if name == "et al." { String::from("others") } else { escape(name) })
+        .collect::<Vec<String>>()
+        .join(" and ");
+}
+
+fn splitKeywords(keywords: &str) -> Vec<String> {
+    return plainText(keywords).split(|c| c == ',' || c == ';').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect();
+}
+
+fn kindToFormat(kind: &str) -> Format {
+    match kind {
+        "book" | "booklet" | "inbook" => return Format::Book,
+        "online" | "electronic" | "www" => return Format::Article,
+        _ => return Format::Paper
+    }
+}
+
+fn formatToKind(format: &Format) -> &'static str {
+    match format {
+        Format::Paper => return "article",
+        Format::Book | Format::Kindle | Format::Ebook | Format::Audiobook => return "book",
+        _ => return "misc"
+    }
+}
+
+fn defaultVenueField(kind: &str) -> &'static str {
+    match kind {
+        "article" => return "journal",
+        "inproceedings" | "conference" | "incollection" => return "booktitle",
+        "book" | "inbook" => return "publisher",
+        _ => return "howpublished"
+    }
+}
+
+/// Maps BibTeX entries onto entries to read. Title and authors become the
+/// title and author, keywords the tags. The key, year, venue, DOI and URL are
+/// kept in fields of the same names, the whole entry in `BIBTEX_FIELD`.
+pub fn fromBibtex(text: &str, fields: &Vec<FieldDefinition>) -> Result<Vec<ReadingEntry>> {
+    let mut entries: Vec<ReadingEntry> = Vec::new();
+    for bib in parseBibtex(text)? {
+        let invalid = |reason: String| Error::Validation(format!("Entry {}: {}", bib.key, reason));
+        let get = |name: &str| bib.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+
+        let title = plainText(&get("title").unwrap_or_default());
+        if title.is_empty() {
+            return Err(invalid(String::from("The title is missing")));
+        }
+
+        let mut values: BTreeMap<String, String> = BTreeMap::new();
+        values.insert(String::from("citekey"), bib.key.clone());
+        let venue = VENUE_FIELDS.iter().find_map(|name| get(name));
+        for (name, value) in vec![("year", get("year")), ("venue", venue), ("doi", get("doi")), ("url", get("url"))] {
+            if let Some(x) = value.map(|x| plainText(&x)).filter(|x| !x.is_empty()) {
+                values.insert(name.to_string(), x);
+            }
+        }
+        for (name, value) in values.iter_mut() {
+            if let Some(def) = fields.iter().find(|def| def.name == *name) {
+                *value = def.parse(value).map_err(|e| invalid(e.to_string()))?;
+            }
+        }
+        values.insert(BIBTEX_FIELD.to_string(), writeEntry(&bib, false));
+
+        let mut re = ReadingEntry {
+            id: -1,
+            title: title,
+            author: displayAuthors(&get("author").or_else(|| get("editor")).unwrap_or_default()),
+            genre: String::new(),
+            format: kindToFormat(&bib.kind),
+            status: Status::Wishlist,
+            tags: splitKeywords(&get("keywords").unwrap_or_default()),
+            created_at: None,
+            updated_at: None,
+            started_at: None,
+            finished_at: None,
+            fields: values
+        };
+        applyDefaults(&mut re, fields);
+        entries.push(re);
+    }
+
+    return Ok(entries);
+}
+
+/// A key like `knuth1984texbook` for entries that have none
+fn generateKey(re: &ReadingEntry) -> String {
+    let ascii = |s: &str| s.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+
+    let family = re.author.split(", ").next().and_then(|name| name.split(' ').last()).map(|x| ascii(x)).unwrap_or_default();
+    let year = re.fields.get("year").map(|x| ascii(x)).unwrap_or_default();
+    let word = re.title.split(' ').map(|w| ascii(w)).find(|w| !w.is_empty() && !["the", "a", "an", "on", "of"].contains(&w.as_str())).unwrap_or_default();
+
+    let key = format!("{}{}{}", family, year, word);
+    if key.is_empty() {
+        return format!("item{}", re.id);
+    }
+    return key;
+}
+
+/// The entry as it would be exported. Values imported are written as they
+/// were unless they have been changed since.
+fn toBibEntry(re: &ReadingEntry) -> BibEntry {
+    let imported = re.fields.get(BIBTEX_FIELD).and_then(|x| parseBibtex(x).ok()).and_then(|x| x.into_iter().next());
+    let imported = imported.unwrap_or(BibEntry { kind: formatToKind(&re.format).to_string(), key: String::new(), fields: Vec::new() });
+    let field = |name: &str| re.fields.get(name).cloned().unwrap_or_default();
+
+    let venueField = VENUE_FIELDS.iter()
+        .find(|name| imported.fields.iter().any(|(n, _)| n == *name))
+        .cloned()
+        .unwrap_or_else(|| defaultVenueField(&imported.kind));
+    let hasAuthor = imported.fields.iter().any(|(n, _)| n == "author") || !imported.fields.iter().any(|(n, _)| n == "editor");
+
+    // the field, its value now and how to write it
+    let mut mapped: Vec<(&str, String, String)> = vec![
+        ("title", re.title.clone(), escape(&re.title)),
+        (if hasAuthor { "author" } else { "editor" }, re.author.clone(), bibtexAuthors(&re.author)),
+        ("year", field("year"), escape(&field("year"))),
+        (venueField, field("venue"), escape(&field("venue"))),
+        ("doi", field("doi"), escape(&field("doi"))),
+        ("url", field("url"), escape(&field("url"))),
+        ("keywords", re.tags.join(", "), escape(&re.tags.join(", ")))
+    ];
+    let unchanged = |name: &str, raw: &str, now: &str| -> bool {
+        match name {
+            "author" | "editor" => return displayAuthors(raw) == now,
+            "keywords" => return splitKeywords(raw).join(", ") == now,
+            _ => return plainText(raw) == now
+        }
+    };
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for (name, raw) in &imported.fields {
+        match mapped.iter().position(|(n, _, _)| n == name) {
+            Some(i) => {
+                let (_, now, written) = mapped.remove(i);
+                if unchanged(name, raw, &now) {
+                    fields.push((name.clone(), raw.clone()));
+                } else if !now.is_empty() {
+                    fields.push((name.clone(), written));
+                }
+            },
+            None => fields.push((name.clone(), raw.clone()))
+        }
+    }
+    for (name, now, written) in mapped {
+        if !now.is_empty() {
+            fields.push((name.to_string(), written));
+        }
+    }
+
+    let key = Some(field("citekey")).filter(|x| !x.is_empty()).unwrap_or(imported.key);
+    return BibEntry { kind: imported.kind, key: key, fields: fields };
+}
+
+/// The entries with unique keys, generated for the entries that have none
+fn toBibEntries(entries: &Vec<ReadingEntry>) -> Vec<BibEntry> {
+    let mut keys: BTreeSet<String> = BTreeSet::new();
+    return entries.iter()
+        .map(|re| {
+            let mut entry = toBibEntry(re);
+            if entry.key.is_empty() {
+                let key = generateKey(re);
+                entry.key = key.clone();
+                for suffix in "abcdefghijklmnopqrstuvwxyz".chars() {
+                    if !keys.contains(&entry.key) {
+                        break;
+                    }
+                    entry.key = format!("{}{}", key, suffix);
+                }
+            }
+            keys.insert(entry.key.clone());
+            return entry;
+        })
+        .collect();
+}
+
+pub fn toBibtex(entries: &Vec<ReadingEntry>) -> String {
+    return toBibEntries(entries).iter().map(|e| writeEntry(e, true)).collect::<Vec<String>>().join("\n");
+}
+
+fn cslType(kind: &str) -> &'static str {
+    match kind {
+        "article" => return "article-journal",
+        "inproceedings" | "conference" => return "paper-conference",
+        "incollection" | "inbook" => return "chapter",
+        "book" | "booklet" => return "book",
+        "techreport" => return "report",
+        "phdthesis" | "mastersthesis" => return "thesis",
+        "online" | "electronic" | "www" => return "webpage",
+        "unpublished" => return "manuscript",
+        _ => return "document"
+    }
+}
+
+/// Renders the entries as CSL-JSON, which Zotero, Pandoc and citeproc read
+pub fn toCslJson(entries: &Vec<ReadingEntry>) -> String {
+    let mut items: Vec<String> = Vec::new();
+    for entry in toBibEntries(entries) {
+        let get = |name: &str| entry.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+        let text = |name: &str| get(name).map(|x| plainText(&x)).filter(|x| !x.is_empty()).map(Value::Text);
+
+        let names = |name: &str| get(name).map(|authors| {
+            let names: Vec<Value> = splitNames(&authors).iter()
+                .filter(|name| *name != "others")
+                .map(|name| match splitName(name) {
+                    (family, given) if given.is_empty() => Value::Record(vec![(String::from("literal"), Value::Text(family))]),
+                    (family, given) => Value::Record(vec![(String::from("family"), Value::Text(family)), (String::from("given"), Value::Text(given))])
+                })
+                .collect();
+            return Value::List(names);
+        });
+        let issued = get("year").map(|x| plainText(&x)).map(|year| match year.parse::<i64>() {
+            Ok(x) => Value::Record(vec![(String::from("date-parts"), Value::List(vec![Value::List(vec![Value::Number(x.to_string())])]))]),
+            Err(_) => Value::Record(vec![(String::from("literal"), Value::Text(year))])
+        });
+
+        let record: Vec<(&str, Option<Value>)> = vec![
+            ("id", Some(Value::Text(entry.key.clone()))),
+            ("type", Some(Value::Text(cslType(&entry.kind).to_string()))),
+            ("title", text("title")),
+            ("author", names("author")),
+            ("editor", names("editor")),
+            ("issued", issued),
+            ("container-title", text("journal").or_else(|| text("booktitle"))),
+            ("publisher", text("publisher")),
+            ("volume", text("volume")),
+            ("issue", text("number")),
+            ("page", text("pages").map(|x| match x { Value::Text(p) => Value::Text(p.replace("--", "-")), _ => x })),
+            ("DOI", text("doi")),
+            ("ISBN", text("isbn")),
+            ("URL", text("url")),
+            ("keyword", text("keywords")),
+            ("abstract", text("abstract")),
+            ("note", text("note"))
+        ];
+        let record: Vec<(String, Value)> = record.into_iter().filter_map(|(k, v)| v.map(|v| (k.to_string(), v))).collect();
+        items.push(format!("  {}", toJson(&Value::Record(record))));
+    }
+
+    if items.is_empty() {
+        return String::from("[]\n");
+    }
+    return format!("[\n{}\n]\n", items.join(",\n"));
+}
+
+#[cfg(test)]
+const BIBLIOGRAPHY: &str = r#"
+Comments outside entries are ignored.
+@string{ tugboat = "TUGboat" }
+@comment{ nothing to see }
+
+@book{knuth1984texbook,
+  title     = {The {\TeX}book},
+  author    = {Knuth, Donald E.},
+  year      = 1984,
+  publisher = {Addison-Wesley},
+  month     = jan,
+  keywords  = {typesetting; tex},
+}
+
+@article( schroedinger,
+  author = "Schr{\"o}dinger, Erwin and {Barnes and Noble} and others",
+  title = {Die gegenw{\"a}rtige Situation in der
+           Quantenmechanik},
+  journal = tugboat # { 23},
+  volume = {23}, number = {48}, pages = {807--812},
+  doi = {10.1007/BF01491891},
+  year = {1935}
+)
+"#;
+
+#[test]
+fn test_bibtex_is_parsed() {
+    let entries = parseBibtex(BIBLIOGRAPHY).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].kind.as_str(), entries[0].key.as_str()), ("book", "knuth1984texbook"));
+    assert_eq!(entries[0].fields[0], (String::from("title"), String::from("The {\\TeX}book")));
+    assert_eq!(entries[0].fields[4], (String::from("month"), String::from("January")));
+    assert_eq!(entries[1].fields[1], (String::from("title"), String::from("Die gegenw{\\\"a}rtige Situation in der Quantenmechanik")));
+    assert_eq!(entries[1].fields[2], (String::from("journal"), String::from("TUGboat 23")));
+
+    assert_eq!(plainText("Die gegenw{\\\"a}rtige {\\emph Situation} \\& \\ss{}e\\'{e}"), "Die gegenwärtige Situation & ßeé");
+    assert_eq!(displayAuthors("Schr{\\\"o}dinger, Erwin and {Barnes and Noble} and others"), "Erwin Schrödinger, Barnes and Noble, et al.");
+    assert_eq!(displayAuthors("Steele, Jr., Guy L. and Donald Knuth"), "Guy L. Steele Jr., Donald Knuth");
+
+    let invalid = vec!["@book{x, title = {Open", "@book{x, title = undefined}", "@book{x, title {Y}}", "@book x"];
+    for text in invalid {
+        assert!(parseBibtex(text).is_err(), "{} should be rejected", text);
+    }
+}
+
+#[test]
+fn test_exported_bibtex_round_trips() {
+    use readinglist::Backend;
+
+    let backend = crate::createFixtureBackend("bibtex");
+    let fields = crate::createFixtureFields();
+    let imported = fromBibtex(BIBLIOGRAPHY, &fields).unwrap();
+    assert_eq!((imported[0].title.as_str(), imported[0].author.as_str(), &imported[0].format), ("The TeXbook", "Donald E. Knuth", &Format::Book));
+    assert_eq!(imported[0].tags, vec!["typesetting", "tex"]);
+    assert_eq!(imported[1].fields.get("venue"), Some(&String::from("TUGboat 23")));
+    assert_eq!(imported[1].fields.get("doi"), Some(&String::from("10.1007/BF01491891")));
+
+    // stored and read back like an import does
+    for (re, _) in crate::import::importEntries(&backend, imported, false).unwrap() {
+        assert_eq!(re.fields.get("citekey").is_some(), true);
+    }
+    let mut stored = vec![backend.getById(7).unwrap(), backend.getById(8).unwrap()];
+    let exported = toBibtex(&stored);
+    assert!(exported.starts_with("@book{knuth1984texbook,\n  title = {The {\\TeX}book},\n  author = {Knuth, Donald E.},\n  year = {1984},\n"), "{}", exported);
+    assert!(exported.contains("  pages = {807--812},\n"), "{}", exported);
+
+    let again: Vec<ReadingEntry> = fromBibtex(&exported, &fields).unwrap();
+    assert_eq!(toBibtex(&again), exported, "Exporting what was imported from an export changes nothing");
+    for (a, b) in again.iter().zip(stored.iter()) {
+        assert_eq!((&a.title, &a.author, &a.tags, &a.format), (&b.title, &b.author, &b.tags, &b.format));
+    }
+
+    // changes win over what was imported
+    stored[0].title = String::from("The TeXbook & more");
+    stored[0].tags.push(String::from("classic"));
+    let changed = toBibtex(&stored);
+    assert!(changed.contains("title = {The TeXbook \\& more},"), "{}", changed);
+    assert!(changed.contains("keywords = {typesetting, tex, classic},"), "{}", changed);
+
+    // entries that weren't imported get a key of their own
+    let others = toBibtex(&vec![backend.getById(1).unwrap(), backend.getById(1).unwrap()]);
+    assert!(others.starts_with("@book{gaimanamerican,\n  title = {American Gods},\n  author = {Neil Gaiman},\n  keywords = {myth, usa},\n}\n"), "{}", others);
+    assert!(others.contains("@book{gaimanamericana,"), "{}", others);
+
+    let csl = toCslJson(&vec![backend.getById(8).unwrap()]);
+    assert!(csl.starts_with("[\n  {\"id\": \"schroedinger\", \"type\": \"article-journal\", \"title\": \"Die gegenwärtige Situation in der Quantenmechanik\", \
+                             \"author\": [{\"family\": \"Schrödinger\", \"given\": \"Erwin\"}, {\"literal\": \"Barnes and Noble\"}], \
+                             \"issued\": {\"date-parts\": [[1935]]}, \"container-title\": \"TUGboat 23\", \"volume\": \"23\", \"issue\": \"48\", \"page\": \"807-812\", \
+                             \"DOI\": \"10.1007/BF01491891\"}"), "{}", csl);
+}
@@ -0,0 +1,822 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::io;
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+
+use tui::Frame;
+use tui::Terminal;
+use tui::backend::TermionBackend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap};
+
+use readinglist::{Backend, Clipping, Error, ReadingEntry, Result, SortDirection, SortKey, Status, STATUSES};
+use readinglist::{statusEnumToString, stringToStatusEnum};
+use readinglist::fields::{FieldDefinition, FieldType};
+
+use crate::config::{Colors, Config};
+use crate::querylanguage::{eval, parse_query, TableRow};
+use crate::{columnTitle, columnValue, createHeaderVec, createTableRowFromReadingEntry, sortOrder};
+use crate::{changeStatusOf, isEditable, setColumn, splitTags};
+
+/// What the keys typed go to
+#[derive(Debug, PartialEq, Clone)]
+enum Mode {
+    Browse,
+    // the query bar, which filters while typing
+    Query,
+    // the input line, editing the value of the named column of the selected entry
+    Edit(String),
+    // the input line, asking for tags to add to or remove from the targets
+    AddTags,
+    RemoveTags
+}
+
+/// State of the full-screen browser, kept apart from the terminal so it can
+/// be tested without one. Every change goes through the backend, the entries
+/// are read again afterwards.
+pub struct Browser<'a> {
+    backend: &'a dyn Backend,
+    entries: Vec<ReadingEntry>,
+    // the rows `eval` sees, one per entry
+    rows: Vec<TableRow>,
+    columns: Vec<String>,
+    fields: Vec<FieldDefinition>,
+    ignoreCase: bool,
+    sort: String,
+    dateFormat: String,
+    colors: Colors,
+
+    mode: Mode,
+    query: String,
+    // restored when editing the query is cancelled
+    queryBeforeEdit: String,
+    queryError: Option<String>,
+    // what has been typed into the input line
+    input: String,
+    // shown in place of the help until the next key press
+    message: Option<String>,
+
+    // the column the cursor is on, an index into `columns`
+    column: usize,
+    // chosen in the browser, takes precedence over the order of the query
+    sortBy: Option<SortKey>,
+
+    // ids of the entries bulk actions apply to. Without any, they apply to
+    // the selected entry.
+    marked: BTreeSet<i64>,
+    // the entries removed by the last deletion with their clippings, until
+    // they are restored
+    deleted: Vec<(ReadingEntry, Vec<Clipping>)>,
+
+    // indices into `entries` of the rows shown, in the order they are shown
+    visible: Vec<usize>,
+    state: TableState,
+    // rows moved by page up and page down, follows the height of the table
+    pageSize: usize
+}
+
+impl<'a> Browser<'a> {
+    pub fn new(backend: &'a dyn Backend, config: &Config) -> Result<Browser<'a>> {
+        let mut browser = Browser {
+            backend: backend,
+            entries: Vec::new(),
+            rows: Vec::new(),
+            columns: config.columns.clone(),
+            fields: config.fields.clone(),
+            ignoreCase: config.ignore_case,
+            sort: config.sort.clone(),
+            dateFormat: config.date_format.clone(),
+            colors: config.colors.clone(),
+            mode: Mode::Browse,
+            query: config.query.clone(),
+            queryBeforeEdit: String::new(),
+            queryError: None,
+            input: String::new(),
+            message: None,
+            column: 0,
+            sortBy: None,
+            marked: BTreeSet::new(),
+            deleted: Vec::new(),
+            visible: Vec::new(),
+            state: TableState::default(),
+            pageSize: 10
+        };
+        browser.reload()?;
+
+        return Ok(browser);
+    }
+
+    /// The entry under the cursor
+    pub fn selected(&self) -> Option<&ReadingEntry> {
+        return self.state.selected().and_then(|i| self.visible.get(i)).map(|i| &self.entries[*i]);
+    }
+
+    /// Reads all entries from the backend again
+    fn reload(&mut self) -> Result<()> {
+        // the visible rows point into the old entries, so they are carried over by id
+        let selectedId = self.selected().map(|re| re.id);
+        let visibleIds: Vec<i64> = self.visible.iter().map(|i| self.entries[*i].id).collect();
+
+        self.entries = self.backend.getAllEntries()?;
+        self.rows = self.entries.iter().map(|re| createTableRowFromReadingEntry(re, &self.fields)).collect();
+        self.visible = visibleIds.iter().filter_map(|id| self.entries.iter().position(|re| re.id == *id)).collect();
+        if let Some(x) = selectedId.and_then(|id| self.visible.iter().position(|i| self.entries[*i].id == id)) {
+            self.state.select(Some(x));
+        }
+
+        let ids: BTreeSet<i64> = self.entries.iter().map(|re| re.id).collect();
+        self.marked.retain(|id| ids.contains(id));
+
+        self.refilter();
+        return Ok(());
+    }
+
+    /// Evaluates the query against all entries again. An invalid query keeps
+    /// the rows of the last valid one, so they don't vanish while typing.
+    fn refilter(&mut self) {
+        let queryColumns = createHeaderVec(&self.fields);
+        let query = match parse_query(&self.query, &queryColumns, self.ignoreCase) {
+            Ok(x) => x,
+            Err(e) => {
+                self.queryError = Some(e.to_string());
+                return;
+            }
+        };
+        let page = match sortOrder(&query, &self.sort, &queryColumns, self.ignoreCase) {
+            Ok(x) => x,
+            Err(e) => {
+                self.queryError = Some(e.to_string());
+                return;
+            }
+        };
+        self.queryError = None;
+
+        let selectedId = self.selected().map(|re| re.id);
+        let selectedRow = self.state.selected().unwrap_or(0);
+
+        let mut visible: Vec<usize> = Vec::new();
+        for i in 0..self.entries.len() {
+            if eval(&query, &mut self.rows[i]) {
+                visible.push(i);
+            }
+        }
+
+        let order = match &self.sortBy {
+            Some(x) => vec![x.clone()],
+            None => page.order.clone()
+        };
+        visible.sort_by(|a, b| {
+            for key in &order {
+                let ordering = compareBy(&self.entries[*a], &self.entries[*b], &key.column, &self.fields);
+                let ordering = if key.direction == SortDirection::Desc { ordering.reverse() } else { ordering };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            return Ordering::Equal;
+        });
+
+        self.visible = visible.into_iter()
+            .skip(page.offset.unwrap_or(0) as usize)
+            .take(page.limit.map(|x| x as usize).unwrap_or(usize::MAX))
+            .collect();
+
+        // stay on the same entry if it is still there, else on the same row
+        let position = selectedId.and_then(|id| self.visible.iter().position(|i| self.entries[*i].id == id));
+        match position {
+            Some(x) => self.state.select(Some(x)),
+            None if self.visible.is_empty() => self.state.select(None),
+            None => self.state.select(Some(std::cmp::min(selectedRow, self.visible.len() - 1)))
+        }
+    }
+
+    fn moveSelection(&mut self, delta: i64) {
+        if self.visible.is_empty() {
+            return;
+        }
+
+        let current = self.state.selected().unwrap_or(0) as i64;
+        let last = self.visible.len() as i64 - 1;
+        self.state.select(Some(std::cmp::min(std::cmp::max(current + delta, 0), last) as usize));
+    }
+
+    /// Sorts by the column under the cursor, ascending first, then descending,
+    /// then back to the order of the query
+    fn toggleSort(&mut self) {
+        let column = self.columns[self.column].clone();
+        self.sortBy = match &self.sortBy {
+            Some(x) if x.column == column && x.direction == SortDirection::Asc => Some(SortKey { column: column, direction: SortDirection::Desc }),
+            Some(x) if x.column == column => None,
+            _ => Some(SortKey { column: column, direction: SortDirection::Asc })
+        };
+        self.refilter();
+    }
+
+    fn toggleMark(&mut self) {
+        if let Some(id) = self.selected().map(|re| re.id) {
+            if !self.marked.remove(&id) {
+                self.marked.insert(id);
+            }
+            self.moveSelection(1);
+        }
+    }
+
+    /// Marks all rows shown or, if they are all marked already, none
+    fn toggleMarkAll(&mut self) {
+        let ids: BTreeSet<i64> = self.visible.iter().map(|i| self.entries[*i].id).collect();
+        if ids.is_subset(&self.marked) {
+            self.marked.clear();
+        } else {
+            self.marked.extend(ids);
+        }
+    }
+
+    /// The ids bulk actions apply to
+    fn targets(&self) -> Vec<i64> {
+        if !self.marked.is_empty() {
+            return self.marked.iter().cloned().collect();
+        }
+        return self.selected().map(|re| vec![re.id]).unwrap_or_default();
+    }
+
+    /// Runs `action` for every target and reports how it went
+    fn applyToTargets<F: Fn(&dyn Backend, i64) -> Result<()>>(&mut self, verb: &str, action: F) {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+
+        let mut failures: Vec<Error> = Vec::new();
+        for id in &targets {
+            if let Err(e) = action(self.backend, *id) {
+                failures.push(e);
+            }
+        }
+
+        self.message = match failures.first() {
+            None => Some(format!("{} {}", verb, items(targets.len()))),
+            Some(e) => Some(format!("{} {} of {}. {}", verb, targets.len() - failures.len(), items(targets.len()), e))
+        };
+        self.refresh();
+    }
+
+    /// Reads the entries again after a change, reporting a failure instead of the change
+    fn refresh(&mut self) {
+        if let Err(e) = self.reload() {
+            self.message = Some(e.to_string());
+        }
+    }
+
+    fn setStatus(&mut self, next: Status) {
+        let verb = format!("Moved to {}:", statusEnumToString(&next));
+        self.applyToTargets(&verb, |backend, id| changeStatusOf(backend, id, next));
+    }
+
+    fn deleteTargets(&mut self) {
+        let targets = self.targets();
+        if targets.is_empty() {
+            return;
+        }
+
+        self.deleted.clear();
+        for id in &targets {
+            let deleted = self.backend.getClippings(*id).and_then(|clippings| Ok((self.backend.deleteById(*id)?, clippings)));
+            match deleted {
+                Ok(x) => self.deleted.push(x),
+                Err(e) => {
+                    self.message = Some(e.to_string());
+                    self.refresh();
+                    return;
+                }
+            }
+        }
+
+        self.marked.clear();
+        self.message = Some(format!("Deleted {}. Press u to undo", items(self.deleted.len())));
+        self.refresh();
+    }
+
+    fn undoDelete(&mut self) {
+        if self.deleted.is_empty() {
+            self.message = Some(String::from("Nothing to undo"));
+            return;
+        }
+
+        let deleted = std::mem::replace(&mut self.deleted, Vec::new());
+        for (re, clippings) in &deleted {
+            let restored = self.backend.restoreEntry(re)
+                .and_then(|_| clippings.iter().map(|c| self.backend.addClipping(c)).collect::<Result<Vec<bool>>>());
+            if let Err(e) = restored {
+                self.message = Some(e.to_string());
+                self.refresh();
+                return;
+            }
+        }
+
+        self.message = Some(format!("Restored {}", items(deleted.len())));
+        self.refresh();
+    }
+
+    /// Starts editing the column under the cursor of the selected entry
+    fn startEdit(&mut self) {
+        let column = self.columns[self.column].clone();
+        let value = match self.selected() {
+            Some(re) => columnValue(re, &column, &self.dateFormat),
+            None => return
+        };
+        if !isEditable(&column, &self.fields) {
+            self.message = Some(format!("{} can not be edited", columnTitle(&column)));
+            return;
+        }
+
+        self.input = value;
+        self.mode = Mode::Edit(column);
+    }
+
+    /// Takes what has been typed into the input line
+    fn commitInput(&mut self) {
+        let input = std::mem::replace(&mut self.input, String::new());
+        let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+        let fields = self.fields.clone();
+
+        match mode {
+            Mode::Edit(column) => {
+                let id = match self.selected() {
+                    Some(re) => re.id,
+                    None => return
+                };
+                let result = self.backend.getById(id)
+                    .and_then(|mut re| setColumn(&mut re, &column, &input, &fields).map(|_| re))
+                    .and_then(|re| self.backend.updateEntry(&re));
+                if let Err(e) = result {
+                    self.message = Some(e.to_string());
+                }
+                self.refresh();
+            },
+            Mode::AddTags => {
+                let tags = splitTags(&input);
+                self.applyToTargets("Tagged", |backend, id| {
+                    let mut re = backend.getById(id)?;
+                    re.tags.extend(tags.iter().cloned());
+                    return backend.updateEntry(&re);
+                });
+            },
+            Mode::RemoveTags => {
+                // stored tags are trimmed, see `Backend::updateEntry`
+                let tags: Vec<String> = splitTags(&input).iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+                self.applyToTargets("Untagged", |backend, id| {
+                    let mut re = backend.getById(id)?;
+                    re.tags.retain(|t| !tags.contains(t));
+                    return backend.updateEntry(&re);
+                });
+            },
+            Mode::Browse | Mode::Query => {}
+        }
+    }
+
+    /// Reacts to a key press. Returns false once the browser should close.
+    pub fn handleKey(&mut self, key: Key) -> bool {
+        match self.mode {
+            Mode::Query => {
+                match key {
+                    Key::Char('\n') => self.mode = Mode::Browse,
+                    Key::Esc => {
+                        self.query = self.queryBeforeEdit.clone();
+                        self.mode = Mode::Browse;
+                        self.refilter();
+                    },
+                    Key::Backspace => {
+                        self.query.pop();
+                        self.refilter();
+                    },
+                    Key::Ctrl('u') => {
+                        self.query.clear();
+                        self.refilter();
+                    },
+                    Key::Char(c) if !c.is_control() => {
+                        self.query.push(c);
+                        self.refilter();
+                    },
+                    _ => {}
+                }
+                return true;
+            },
+            Mode::Edit(_) | Mode::AddTags | Mode::RemoveTags => {
+                match key {
+                    Key::Char('\n') => self.commitInput(),
+                    Key::Esc => {
+                        self.input.clear();
+                        self.mode = Mode::Browse;
+                    },
+                    Key::Backspace => { self.input.pop(); },
+                    Key::Ctrl('u') => self.input.clear(),
+                    Key::Char(c) if !c.is_control() => self.input.push(c),
+                    _ => {}
+                }
+                return true;
+            },
+            Mode::Browse => {}
+        }
+
+        self.message = None;
+        match key {
+            Key::Char('q') | Key::Ctrl('c') => return false,
+            // Esc lets go of the marked rows first
+            Key::Esc if !self.marked.is_empty() => self.marked.clear(),
+            Key::Esc => return false,
+            Key::Char('/') => {
+                self.queryBeforeEdit = self.query.clone();
+                self.mode = Mode::Query;
+            },
+            Key::Down | Key::Char('j') => self.moveSelection(1),
+            Key::Up | Key::Char('k') => self.moveSelection(-1),
+            Key::PageDown => self.moveSelection(self.pageSize as i64),
+            Key::PageUp => self.moveSelection(-(self.pageSize as i64)),
+            Key::Home | Key::Char('g') => self.moveSelection(i64::MIN / 2),
+            Key::End | Key::Char('G') => self.moveSelection(i64::MAX / 2),
+            Key::Left | Key::Char('h') => self.column = self.column.saturating_sub(1),
+            Key::Right | Key::Char('l') => self.column = std::cmp::min(self.column + 1, self.columns.len() - 1),
+            Key::Char('s') => self.toggleSort(),
+            Key::Char('e') | Key::Char('\n') => self.startEdit(),
+            Key::Char(' ') => self.toggleMark(),
+            Key::Char('a') => self.toggleMarkAll(),
+            Key::Char('t') if !self.targets().is_empty() => self.mode = Mode::AddTags,
+            Key::Char('T') if !self.targets().is_empty() => self.mode = Mode::RemoveTags,
+            Key::Char('d') => self.deleteTargets(),
+            Key::Char('u') => self.undoDelete(),
+            // the statuses in the order of their lifecycle
+            Key::Char(c) if ('1'..='6').contains(&c) => {
+                let status = STATUSES[c as usize - '1' as usize];
+                self.setStatus(stringToStatusEnum(status).unwrap());
+            },
+            _ => {}
+        }
+        return true;
+    }
+}
+
+fn items(n: usize) -> String {
+    if n == 1 {
+        return String::from("1 item");
+    }
+    return format!("{} items", n);
+}
+
+/// Compares two entries by a column like `order by` does: numbers by their
+/// value, dates by time and everything else ignoring case. Missing values
+/// come first.
+fn compareBy(a: &ReadingEntry, b: &ReadingEntry, column: &str, fields: &Vec<FieldDefinition>) -> Ordering {
+    match column {
+        "id" => return a.id.cmp(&b.id),
+        "created" => return a.created_at.cmp(&b.created_at),
+        "updated" => return a.updated_at.cmp(&b.updated_at),
+        "started" => return a.started_at.cmp(&b.started_at),
+        "finished" => return a.finished_at.cmp(&b.finished_at),
+        _ => {}
+    }
+
+    if let Some(FieldType::Number) = fields.iter().find(|def| def.name == column).map(|def| &def.t) {
+        let number = |re: &ReadingEntry| re.fields.get(column).and_then(|x| x.parse::<f64>().ok());
+        return number(a).partial_cmp(&number(b)).unwrap_or(Ordering::Equal);
+    }
+
+    return columnValue(a, column, "").to_lowercase().cmp(&columnValue(b, column, "").to_lowercase());
+}
+
+/// Maps the colour names of the config file onto the colours of the terminal
+fn tuiColor(name: &str) -> Option<Color> {
+    match name {
+        "black" => return Some(Color::Black),
+        "dark_grey" => return Some(Color::DarkGray),
+        "red" => return Some(Color::LightRed),
+        "dark_red" => return Some(Color::Red),
+        "green" => return Some(Color::LightGreen),
+        "dark_green" => return Some(Color::Green),
+        "yellow" => return Some(Color::LightYellow),
+        "dark_yellow" => return Some(Color::Yellow),
+        "blue" => return Some(Color::LightBlue),
+        "dark_blue" => return Some(Color::Blue),
+        "magenta" => return Some(Color::LightMagenta),
+        "dark_magenta" => return Some(Color::Magenta),
+        "cyan" => return Some(Color::LightCyan),
+        "dark_cyan" => return Some(Color::Cyan),
+        "white" => return Some(Color::White),
+        "grey" => return Some(Color::Gray),
+        _ => return None
+    }
+}
+
+fn draw<B: tui::backend::Backend>(f: &mut Frame<B>, browser: &mut Browser) {
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(f.size());
+    let main = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+        .split(areas[1]);
+
+    drawQueryBar(f, browser, areas[0]);
+    drawTable(f, browser, main[0]);
+    drawDetails(f, browser, main[1]);
+
+    drawStatusLine(f, browser, areas[2]);
+}
+
+/// Shows the input line while asking for a value, else the last message or some help
+fn drawStatusLine<B: tui::backend::Backend>(f: &mut Frame<B>, browser: &Browser, area: tui::layout::Rect) {
+    let prompt = match &browser.mode {
+        Mode::Edit(column) => Some(format!("{}: ", columnTitle(column))),
+        Mode::AddTags => Some(format!("Add tags to {}: ", items(browser.targets().len()))),
+        Mode::RemoveTags => Some(format!("Remove tags from {}: ", items(browser.targets().len()))),
+        Mode::Browse | Mode::Query => None
+    };
+    if let Some(prompt) = prompt {
+        let line = format!(" {}{}", prompt, browser.input);
+        f.set_cursor(area.x + line.chars().count() as u16, area.y);
+        f.render_widget(Paragraph::new(line), area);
+        return;
+    }
+
+    let help = match (&browser.mode, &browser.message) {
+        (Mode::Query, _) => String::from("enter apply  esc cancel  ctrl-u clear"),
+        (_, Some(x)) => x.clone(),
+        _ => String::from("/ query  e edit  1-6 status  space mark  t/T tag  d delete  s sort  q quit")
+    };
+    let marked = if browser.marked.is_empty() { String::new() } else { format!(", {} marked", browser.marked.len()) };
+    let status = format!(" {} of {} items{}   {}", browser.visible.len(), browser.entries.len(), marked, help);
+    f.render_widget(Paragraph::new(status).style(Style::default().add_modifier(Modifier::REVERSED)), area);
+}
+
+fn drawQueryBar<B: tui::backend::Backend>(f: &mut Frame<B>, browser: &Browser, area: tui::layout::Rect) {
+    let (title, style) = match &browser.queryError {
+        Some(e) => (format!("Query: {}", e), Style::default().fg(Color::LightRed)),
+        None => (String::from("Query"), Style::default())
+    };
+    let borderStyle = if browser.mode == Mode::Query { Style::default().fg(Color::LightYellow) } else { Style::default() };
+
+    let bar = Paragraph::new(browser.query.clone())
+        .block(Block::default().borders(Borders::ALL).border_style(borderStyle).title(Span::styled(title, style)));
+    f.render_widget(bar, area);
+
+    if browser.mode == Mode::Query {
+        f.set_cursor(area.x + 1 + browser.query.chars().count() as u16, area.y + 1);
+    }
+}
+
+fn drawTable<B: tui::backend::Backend>(f: &mut Frame<B>, browser: &mut Browser, area: tui::layout::Rect) {
+    let colored = |style: Style, color: Option<&String>| -> Style {
+        match color.and_then(|x| tuiColor(x)) {
+            Some(x) if browser.colors.enabled => return style.fg(x),
+            _ => return style
+        }
+    };
+
+    let headerStyle = colored(Style::default().add_modifier(Modifier::BOLD), browser.colors.header.as_ref());
+    // the first column shows which rows are marked
+    let header = Row::new(std::iter::once(Cell::from("")).chain(browser.columns.iter().enumerate().map(|(i, col)| {
+        let arrow = match &browser.sortBy {
+            Some(x) if &x.column == col && x.direction == SortDirection::Asc => " ▲",
+            Some(x) if &x.column == col => " ▼",
+            _ => ""
+        };
+        let style = if i == browser.column { headerStyle.add_modifier(Modifier::UNDERLINED) } else { headerStyle };
+        return Cell::from(format!("{}{}", columnTitle(col), arrow)).style(style);
+    })));
+
+    let values: Vec<Vec<String>> = browser.visible.iter()
+        .map(|i| browser.columns.iter().map(|col| columnValue(&browser.entries[*i], col, &browser.dateFormat)).collect())
+        .collect();
+
+    // as wide as the widest value, but long titles mustn't push everything else out
+    let mut widths: Vec<Constraint> = vec![Constraint::Length(1)];
+    widths.extend(browser.columns.iter().enumerate()
+        .map(|(c, col)| {
+            let widest = values.iter().map(|row| row[c].chars().count()).max().unwrap_or(0);
+            return Constraint::Length(std::cmp::min(std::cmp::max(widest, columnTitle(col).chars().count() + 2), 40) as u16);
+        }));
+
+    let rows = browser.visible.iter().zip(values.iter()).map(|(i, row)| {
+        let re = &browser.entries[*i];
+        let mark = if browser.marked.contains(&re.id) { "●" } else { " " };
+        let style = colored(Style::default(), browser.colors.statuses.get(&statusEnumToString(&re.status)));
+        return Row::new(std::iter::once(mark.to_string()).chain(row.iter().cloned())).style(style);
+    });
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title("Reading list"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .widths(&widths);
+
+    // borders and header
+    browser.pageSize = std::cmp::max(area.height.saturating_sub(3) as usize, 1);
+    f.render_stateful_widget(table, area, &mut browser.state);
+}
+
+fn drawDetails<B: tui::backend::Backend>(f: &mut Frame<B>, browser: &Browser, area: tui::layout::Rect) {
+    let mut lines: Vec<Spans> = Vec::new();
+    if let Some(re) = browser.selected() {
+        let mut columns: Vec<String> = vec!["title", "author", "genre", "format", "status", "tags"].iter().map(|x| x.to_string()).collect();
+        columns.extend(browser.fields.iter().map(|def| def.name.clone()));
+        columns.extend(vec!["created", "updated", "started", "finished", "id"].iter().map(|x| x.to_string()));
+
+        for col in &columns {
+            lines.push(Spans::from(vec![
+                Span::styled(format!("{}: ", columnTitle(col)), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(columnValue(re, col, &browser.dateFormat))
+            ]));
+        }
+    }
+
+    let details = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, area);
+}
+
+/// Opens the browser on the whole terminal until the user quits
+pub fn run(backend: &dyn Backend, config: &Config) -> Result<()> {
+    let mut browser = Browser::new(backend, config)?;
+
+    let stdout = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut terminal = Terminal::new(TermionBackend::new(stdout))?;
+
+    terminal.draw(|f| draw(f, &mut browser))?;
+    for key in io::stdin().keys() {
+        if !browser.handleKey(key?) {
+            break;
+        }
+        terminal.draw(|f| draw(f, &mut browser))?;
+    }
+    terminal.show_cursor()?;
+
+    return Ok(());
+}
+
+#[cfg(test)]
+fn typeKeys(browser: &mut Browser, text: &str) {
+    for c in text.chars() {
+        browser.handleKey(Key::Char(c));
+    }
+}
+
+/// An entry with only the values a test looks at
+#[cfg(test)]
+fn createEntry(title: &str, author: &str, status: Status, tags: Vec<&str>, fields: Vec<(&str, &str)>) -> ReadingEntry {
+    return ReadingEntry {
+        id: -1,
+        title: title.to_string(),
+        author: author.to_string(),
+        genre: String::new(),
+        format: readinglist::Format::Book,
+        status: status,
+        tags: tags.iter().map(|x| x.to_string()).collect(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    };
+}
+
+#[test]
+fn test_query_bar_refilters_while_typing() {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    backend.addEntry(&createEntry("American Gods", "Neil Gaiman", Status::Wishlist, vec![], vec![("source", "library")])).unwrap();
+    backend.addEntry(&createEntry("Good Omens", "Neil Gaiman", Status::Wishlist, vec![], vec![("source", "friend")])).unwrap();
+    backend.addEntry(&createEntry("Mort", "Terry Pratchett", Status::Wishlist, vec![], vec![])).unwrap();
+    let config = Config::new(vec![FieldDefinition::new("source", FieldType::Text, None).unwrap()]);
+    let mut browser = Browser::new(&backend, &config).unwrap();
+    let ids = |b: &Browser| -> Vec<i64> { b.visible.iter().map(|i| b.entries[*i].id).collect() };
+
+    assert_eq!(ids(&browser), vec![1, 2, 3]);
+
+    browser.handleKey(Key::Char('/'));
+    typeKeys(&mut browser, "author is \"Neil Gaiman\"");
+    assert_eq!(ids(&browser), vec![1, 2]);
+
+    // half typed queries keep the last rows
+    typeKeys(&mut browser, " and sour");
+    assert!(browser.queryError.is_some());
+    assert_eq!(ids(&browser), vec![1, 2]);
+
+    typeKeys(&mut browser, "ce is library");
+    assert!(browser.queryError.is_none());
+    assert_eq!(ids(&browser), vec![1]);
+
+    // cancelling brings back the query from before
+    browser.handleKey(Key::Esc);
+    assert_eq!(browser.query, "");
+    assert_eq!(ids(&browser), vec![1, 2, 3]);
+    assert!(browser.handleKey(Key::Char('j')));
+    assert_eq!(browser.selected().map(|re| re.id), Some(2));
+    assert!(!browser.handleKey(Key::Char('q')));
+}
+
+#[test]
+fn test_columns_can_be_sorted() {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    backend.addEntry(&createEntry("Good Omens", "", Status::Wishlist, vec![], vec![("pages", "412")])).unwrap();
+    backend.addEntry(&createEntry("Mort", "", Status::Wishlist, vec![], vec![("pages", "96")])).unwrap();
+    backend.addEntry(&createEntry("Antifragile", "", Status::Wishlist, vec![], vec![])).unwrap();
+    let mut config = Config::new(vec![FieldDefinition::new("pages", FieldType::Number, None).unwrap()]);
+    config.sort = String::from("title desc");
+    let mut browser = Browser::new(&backend, &config).unwrap();
+    let ids = |b: &Browser| -> Vec<i64> { b.visible.iter().map(|i| b.entries[*i].id).collect() };
+
+    // the configured sort applies until a column is chosen
+    assert_eq!(ids(&browser), vec![2, 1, 3]);
+    assert_eq!(browser.selected().map(|re| re.id), Some(2));
+
+    let pages = browser.columns.iter().position(|c| c == "pages").unwrap();
+    for _ in 0..pages {
+        browser.handleKey(Key::Right);
+    }
+    // by value, not as text, entries without one come first
+    browser.handleKey(Key::Char('s'));
+    assert_eq!(ids(&browser), vec![3, 2, 1]);
+    // the cursor stays on the entry it was on
+    assert_eq!(browser.selected().map(|re| re.id), Some(2));
+
+    browser.handleKey(Key::Char('s'));
+    assert_eq!(ids(&browser), vec![1, 2, 3]);
+
+    browser.handleKey(Key::Char('s'));
+    assert_eq!(ids(&browser), vec![2, 1, 3]);
+}
+
+#[test]
+fn test_changes_apply_to_the_marked_entries() {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    backend.addEntry(&createEntry("American Gods", "Neil Gaiman", Status::Finished, vec![], vec![])).unwrap();
+    backend.addEntry(&createEntry("Good Omens", "Neil Gaiman", Status::Reading, vec!["humor"], vec![])).unwrap();
+    backend.addEntry(&createEntry("Mort", "Terry Pratchett", Status::Wishlist, vec![], vec![("note", "Rincewind!")])).unwrap();
+    let config = Config::new(vec![FieldDefinition::new("note", FieldType::Text, None).unwrap()]);
+    let mut browser = Browser::new(&backend, &config).unwrap();
+    let statusOf = |id: i64| backend.getById(id).unwrap().status;
+    let ids = |b: &Browser| -> Vec<i64> { b.visible.iter().map(|i| b.entries[*i].id).collect() };
+
+    // editing the cell under the cursor
+    browser.handleKey(Key::Right);
+    browser.handleKey(Key::Char('e'));
+    browser.handleKey(Key::Ctrl('u'));
+    typeKeys(&mut browser, "N. Gaiman\n");
+    assert_eq!(backend.getById(1).unwrap().author, "N. Gaiman");
+
+    browser.handleKey(Key::Right);
+    browser.handleKey(Key::Right);
+    browser.handleKey(Key::Char('e'));
+    typeKeys(&mut browser, "x\n");
+    assert!(browser.message.is_some(), "Unknown statuses are rejected");
+    assert_eq!(statusOf(1), Status::Finished);
+
+    // 2 is being read, 3 is on the wishlist and can't be finished right away
+    browser.handleKey(Key::Down);
+    browser.handleKey(Key::Char(' '));
+    browser.handleKey(Key::Char(' '));
+    assert_eq!(browser.targets(), vec![2, 3]);
+    browser.handleKey(Key::Char('5'));
+    assert_eq!((statusOf(2), statusOf(3)), (Status::Finished, Status::Wishlist));
+    assert!(browser.message.as_ref().unwrap().contains("1 of 2 items"), "{:?}", browser.message);
+
+    typeKeys(&mut browser, "tclassic, to lend\n");
+    assert_eq!(backend.getById(3).unwrap().tags, vec!["classic", "to lend"]);
+    typeKeys(&mut browser, "Tto lend\n");
+    assert_eq!(backend.getById(3).unwrap().tags, vec!["classic"]);
+    assert_eq!(backend.getById(2).unwrap().tags, vec!["humor", "classic"]);
+
+    // deleting keeps the entries around until the next deletion
+    let clipping = Clipping { entry_id: 3, kind: readinglist::ClippingKind::Note, location: String::from("12"), page: String::new(), added_at: None, content: String::from("Rincewind?") };
+    backend.addClipping(&clipping).unwrap();
+    let before = backend.getById(3).unwrap();
+    browser.handleKey(Key::Char('d'));
+    assert!(matches!(backend.getById(2), Err(Error::NotFound(2))));
+    assert!(matches!(backend.getById(3), Err(Error::NotFound(3))));
+    assert!(browser.marked.is_empty());
+    assert_eq!(ids(&browser), vec![1]);
+    assert_eq!(browser.selected().map(|re| re.id), Some(1));
+
+    browser.handleKey(Key::Char('u'));
+    let after = backend.getById(3).unwrap();
+    assert_eq!((after.title, after.tags, after.fields), (before.title, before.tags, before.fields));
+    assert_eq!(backend.getClippings(3).unwrap(), vec![clipping]);
+    assert_eq!(ids(&browser), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_several_tags_are_removed_at_once() {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    backend.addEntry(&createEntry("Mort", "Terry Pratchett", Status::Wishlist, vec!["fantasy", "humor", "disc world"], vec![])).unwrap();
+    let mut browser = Browser::new(&backend, &Config::new(Vec::new())).unwrap();
+
+    typeKeys(&mut browser, "Thumor,  disc world \n");
+    assert_eq!(backend.getById(1).unwrap().tags, vec!["fantasy"]);
+    assert_eq!(browser.message, Some(String::from("Untagged 1 item")));
+}
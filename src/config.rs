@@ -1,6 +1,520 @@
+use std::collections::BTreeMap;
+
+use comfy_table::Color;
+
+use readinglist::{Error, Result, STATUSES};
+use readinglist::fields::{FieldDefinition, stringToFieldType};
+
+use crate::export::TEMPLATE_FORMATS;
+
 pub struct Config {
     pub debug: bool,
+    pub config_file: String,
     pub db_file_location: String,
-    pub withId: bool,
-    pub query: String
+    pub query: String,
+    pub ignore_case: bool,
+    // the columns of the table in the order they are shown, named like in queries
+    pub columns: Vec<String>,
+    // keys of an `order by` clause, used when the query has none
+    pub sort: String,
+    pub date_format: String,
+    pub colors: Colors,
+    // paths of the templates used by `export`, keyed by the format
+    pub templates: BTreeMap<String, String>,
+    pub fields: Vec<FieldDefinition>,
+    // where each setting came from, keyed like in the config file
+    pub sources: BTreeMap<String, Source>
+}
+
+/// Colours of the table. Only applied when printing to a terminal.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Colors {
+    pub enabled: bool,
+    pub header: Option<String>,
+    // the colour of the rows of items in a status, keyed by the status
+    pub statuses: BTreeMap<String, String>
+}
+
+/// Where the value of a setting came from
+#[derive(Debug, PartialEq, Clone)]
+pub enum Source {
+    Default,
+    Environment(String),
+    File(String),
+    CommandLine
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Environment(name) => write!(f, "${}", name),
+            Source::File(path) => write!(f, "config file {}", path),
+            Source::CommandLine => write!(f, "command line")
+        }
+    }
+}
+
+/// Settings given on the command line. They take precedence over the config
+/// file, `None` and `false` leave the setting to it.
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub db_file_location: Option<String>,
+    pub query: Option<String>,
+    pub ignore_case: bool,
+    pub columns: Option<Vec<String>>,
+    // appended to the columns unless shown already, e.g. by `--id`
+    pub extraColumns: Vec<String>,
+    pub sort: Option<String>,
+    pub date_format: Option<String>,
+    pub noColor: bool
+}
+
+pub const DEFAULT_DB_FILE: &str = "~/rdnglst/readinglist.db";
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// Custom fields are shown after these unless the columns are configured
+pub const DEFAULT_COLUMNS: [&str; 6] = ["title", "author", "genre", "status", "format", "tags"];
+
+/// The settings in the order `config show` prints them
+pub const SETTINGS: [&str; 10] = ["config_file", "db_file", "query", "ignore_case", "columns", "sort", "date_format", "colors", "templates", "fields"];
+
+const COLORS: [(&str, Color); 16] = [
+    ("black", Color::Black),
+    ("dark_grey", Color::DarkGrey),
+    ("red", Color::Red),
+    ("dark_red", Color::DarkRed),
+    ("green", Color::Green),
+    ("dark_green", Color::DarkGreen),
+    ("yellow", Color::Yellow),
+    ("dark_yellow", Color::DarkYellow),
+    ("blue", Color::Blue),
+    ("dark_blue", Color::DarkBlue),
+    ("magenta", Color::Magenta),
+    ("dark_magenta", Color::DarkMagenta),
+    ("cyan", Color::Cyan),
+    ("dark_cyan", Color::DarkCyan),
+    ("white", Color::White),
+    ("grey", Color::Grey)
+];
+
+pub fn stringToColor(name: &str) -> Option<Color> {
+    return COLORS.iter().find(|(n, _)| *n == name).map(|(_, c)| *c);
+}
+
+impl Config {
+    /// The configuration used when neither the config file nor the command
+    /// line say otherwise
+    pub fn new(fields: Vec<FieldDefinition>) -> Config {
+        let mut columns: Vec<String> = DEFAULT_COLUMNS.iter().map(|x| x.to_string()).collect();
+        columns.extend(fields.iter().map(|def| def.name.clone()));
+
+        let sources = SETTINGS.iter().map(|x| (x.to_string(), Source::Default)).collect();
+
+        return Config {
+            debug: false,
+            config_file: DEFAULT_CONFIG_FILE.to_string(),
+            db_file_location: DEFAULT_DB_FILE.to_string(),
+            query: String::new(),
+            ignore_case: false,
+            columns: columns,
+            sort: String::new(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            colors: Colors { enabled: true, header: None, statuses: BTreeMap::new() },
+            templates: BTreeMap::new(),
+            fields: fields,
+            sources: sources
+        };
+    }
+
+    /// Takes the settings present in the config file `doc` read from `path`.
+    /// `knownColumns` are the columns the table can show.
+    pub fn applyConfigFile(&mut self, doc: &toml::Value, path: &str, knownColumns: &Vec<String>) -> Result<()> {
+        let source = Source::File(path.to_string());
+        let invalid = |key: &str, expected: &str| Error::Validation(format!("'{}' in the config file {} has to be {}", key, path, expected));
+
+        if !self.fields.is_empty() {
+            self.sources.insert("fields".to_string(), source.clone());
+        }
+
+        if let Some(x) = doc.get("db_file") {
+            self.db_file_location = x.as_str().ok_or_else(|| invalid("db_file", "a string"))?.to_string();
+            self.sources.insert("db_file".to_string(), source.clone());
+        }
+        if let Some(x) = doc.get("query") {
+            self.query = x.as_str().ok_or_else(|| invalid("query", "a string"))?.to_string();
+            self.sources.insert("query".to_string(), source.clone());
+        }
+        if let Some(x) = doc.get("ignore_case") {
+            self.ignore_case = x.as_bool().ok_or_else(|| invalid("ignore_case", "true or false"))?;
+            self.sources.insert("ignore_case".to_string(), source.clone());
+        }
+        if let Some(x) = doc.get("columns") {
+            let columns = x.as_array()
+                .and_then(|a| a.iter().map(|v| v.as_str().map(|s| s.to_string())).collect::<Option<Vec<String>>>())
+                .ok_or_else(|| invalid("columns", "a list of column names"))?;
+            self.columns = checkColumns(columns, knownColumns)?;
+            self.sources.insert("columns".to_string(), source.clone());
+        }
+        if let Some(x) = doc.get("sort") {
+            self.sort = x.as_str().ok_or_else(|| invalid("sort", "a string"))?.to_string();
+            self.sources.insert("sort".to_string(), source.clone());
+        }
+        if let Some(x) = doc.get("date_format") {
+            self.date_format = checkDateFormat(x.as_str().ok_or_else(|| invalid("date_format", "a string"))?)?;
+            self.sources.insert("date_format".to_string(), source.clone());
+        }
+
+        match doc.get("colors") {
+            Some(toml::Value::Table(table)) => {
+                for (key, value) in table {
+                    if key == "enabled" {
+                        self.colors.enabled = value.as_bool().ok_or_else(|| invalid("colors.enabled", "true or false"))?;
+                        self.sources.insert("colors.enabled".to_string(), source.clone());
+                        continue;
+                    }
+
+                    if key != "header" && !STATUSES.contains(&key.as_str()) {
+                        return Err(Error::Validation(format!("Unknown colour '{}' in the config file {}. Use header or one of {}", key, path, STATUSES.join(", "))));
+                    }
+                    let color = value.as_str().ok_or_else(|| invalid(&format!("colors.{}", key), "a string"))?;
+                    if stringToColor(color).is_none() {
+                        let names: Vec<&str> = COLORS.iter().map(|(n, _)| *n).collect();
+                        return Err(Error::Validation(format!("Unknown colour '{}' for '{}'. Use one of {}", color, key, names.join(", "))));
+                    }
+
+                    if key == "header" {
+                        self.colors.header = Some(color.to_string());
+                    } else {
+                        self.colors.statuses.insert(key.clone(), color.to_string());
+                    }
+                    self.sources.insert(format!("colors.{}", key), source.clone());
+                }
+            },
+            Some(_) => return Err(invalid("colors", "a table")),
+            None => {}
+        }
+
+        match doc.get("templates") {
+            Some(toml::Value::Table(table)) => {
+                for (key, value) in table {
+                    if !TEMPLATE_FORMATS.contains(&key.as_str()) {
+                        return Err(Error::Validation(format!("Unknown template '{}' in the config file {}. Use one of {}", key, path, TEMPLATE_FORMATS.join(", "))));
+                    }
+                    let template = value.as_str().ok_or_else(|| invalid(&format!("templates.{}", key), "the path of a file"))?;
+                    self.templates.insert(key.clone(), template.to_string());
+                    self.sources.insert(format!("templates.{}", key), source.clone());
+                }
+            },
+            Some(_) => return Err(invalid("templates", "a table")),
+            None => {}
+        }
+
+        return Ok(());
+    }
+
+    /// Takes the settings given on the command line
+    pub fn applyOverrides(&mut self, overrides: Overrides, knownColumns: &Vec<String>) -> Result<()> {
+        if let Some(x) = overrides.db_file_location {
+            self.db_file_location = x;
+            self.sources.insert("db_file".to_string(), Source::CommandLine);
+        }
+        if let Some(x) = overrides.query {
+            self.query = x;
+            self.sources.insert("query".to_string(), Source::CommandLine);
+        }
+        if overrides.ignore_case {
+            self.ignore_case = true;
+            self.sources.insert("ignore_case".to_string(), Source::CommandLine);
+        }
+        if let Some(x) = overrides.columns {
+            self.columns = checkColumns(x, knownColumns)?;
+            self.sources.insert("columns".to_string(), Source::CommandLine);
+        }
+        for col in overrides.extraColumns {
+            if !self.columns.contains(&col) {
+                self.columns.push(col);
+                self.sources.insert("columns".to_string(), Source::CommandLine);
+            }
+        }
+        if let Some(x) = overrides.sort {
+            self.sort = x;
+            self.sources.insert("sort".to_string(), Source::CommandLine);
+        }
+        if let Some(x) = overrides.date_format {
+            self.date_format = checkDateFormat(&x)?;
+            self.sources.insert("date_format".to_string(), Source::CommandLine);
+        }
+        if overrides.noColor {
+            self.colors.enabled = false;
+            self.sources.insert("colors.enabled".to_string(), Source::CommandLine);
+        }
+
+        return Ok(());
+    }
+
+    /// Renders the effective settings as TOML, each followed by where it came from
+    pub fn show(&self) -> String {
+        let quote = |x: &str| toml::Value::String(x.to_string()).to_string();
+        let list = |xs: &Vec<String>| format!("[{}]", xs.iter().map(|x| quote(x)).collect::<Vec<String>>().join(", "));
+
+        // colours are looked up one by one, e.g. `colors.header`
+        let sourceOf = |key: &str| self.sources.get(key).cloned().unwrap_or(Source::Default).to_string();
+
+        let mut lines: Vec<(String, String)> = Vec::new();
+        for setting in SETTINGS.iter() {
+            let source = sourceOf(setting);
+            match *setting {
+                "config_file" => lines.push((format!("config_file = {}", quote(&self.config_file)), source)),
+                "db_file" => lines.push((format!("db_file = {}", quote(&self.db_file_location)), source)),
+                "query" => lines.push((format!("query = {}", quote(&self.query)), source)),
+                "ignore_case" => lines.push((format!("ignore_case = {}", self.ignore_case), source)),
+                "columns" => lines.push((format!("columns = {}", list(&self.columns)), source)),
+                "sort" => lines.push((format!("sort = {}", quote(&self.sort)), source)),
+                "date_format" => lines.push((format!("date_format = {}", quote(&self.date_format)), source)),
+                "colors" => {
+                    lines.push((format!("colors.enabled = {}", self.colors.enabled), sourceOf("colors.enabled")));
+                    if let Some(x) = &self.colors.header {
+                        lines.push((format!("colors.header = {}", quote(x)), sourceOf("colors.header")));
+                    }
+                    for (status, color) in &self.colors.statuses {
+                        lines.push((format!("colors.{} = {}", status, quote(color)), sourceOf(&format!("colors.{}", status))));
+                    }
+                },
+                "templates" => {
+                    for (format, path) in &self.templates {
+                        lines.push((format!("templates.{} = {}", format, quote(path)), sourceOf(&format!("templates.{}", format))));
+                    }
+                },
+                "fields" => {
+                    let names: Vec<String> = self.fields.iter().map(|def| def.name.clone()).collect();
+                    lines.push((format!("fields = {}", list(&names)), source));
+                },
+                _ => {}
+            }
+        }
+
+        let width = lines.iter().map(|(line, _)| line.chars().count()).max().unwrap_or(0);
+        return lines.iter()
+            .map(|(line, source)| format!("{:width$}  # {}", line, source, width = width))
+            .collect::<Vec<String>>()
+            .join("\n");
+    }
+}
+
+fn checkColumns(columns: Vec<String>, knownColumns: &Vec<String>) -> Result<Vec<String>> {
+    let columns: Vec<String> = columns.iter().map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()).collect();
+    for col in &columns {
+        if !knownColumns.contains(col) {
+            return Err(Error::Validation(format!("Unknown column '{}'. Use one of {}", col, knownColumns.join(", "))));
+        }
+    }
+    if columns.is_empty() {
+        return Err(Error::Validation(String::from("At least one column has to be shown")));
+    }
+
+    return Ok(columns);
+}
+
+fn checkDateFormat(format: &str) -> Result<String> {
+    use chrono::format::{Item, StrftimeItems};
+
+    // chrono panics when rendering an invalid format, so better find out now
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(Error::Validation(format!("Invalid date format '{}'", format)));
+    }
+
+    return Ok(format.to_string());
+}
+
+/// Where the config file is looked for unless $RDNGLST_CONFIG names another one
+pub const DEFAULT_CONFIG_FILE: &str = "~/.config/rdnglst/config.toml";
+
+pub fn configFileLocation() -> (String, Source) {
+    match std::env::var("RDNGLST_CONFIG") {
+        Ok(x) if !x.is_empty() => return (x, Source::Environment(String::from("RDNGLST_CONFIG"))),
+        _ => return (DEFAULT_CONFIG_FILE.to_string(), Source::Default)
+    }
+}
+
+/// Parses the config file. A missing file is the same as an empty one.
+pub fn readConfigFile(path: &str) -> Result<toml::Value> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Error::Validation(format!("Could not read the config file {}: {}", path, e)))
+    };
+
+    match content.parse::<toml::Value>() {
+        Ok(x) => return Ok(x),
+        Err(e) => return Err(Error::Validation(format!("Invalid config file {}: {}", path, e)))
+    }
+}
+
+/// Reads the custom fields declared as `[[fields]]` tables. Names in
+/// `reserved` are taken by builtin columns or the query language.
+pub fn parseFieldDefinitions(doc: &toml::Value, reserved: &Vec<String>) -> Result<Vec<FieldDefinition>> {
+    let tables = match doc.get("fields") {
+        Some(toml::Value::Array(x)) => x.clone(),
+        Some(_) => return Err(Error::Validation(String::from("Custom fields have to be declared as [[fields]] tables"))),
+        None => Vec::new()
+    };
+
+    let mut definitions: Vec<FieldDefinition> = Vec::new();
+    for table in &tables {
+        let name = match table.get("name") {
+            Some(toml::Value::String(x)) => x.clone(),
+            _ => return Err(Error::Validation(String::from("Every custom field needs a name")))
+        };
+        if reserved.contains(&name) || definitions.iter().any(|d| d.name == name) {
+            return Err(Error::Validation(format!("The field name '{}' is taken already", name)));
+        }
+
+        let t = match table.get("type") {
+            Some(toml::Value::String(x)) => x.clone(),
+            _ => return Err(Error::Validation(format!("The custom field '{}' needs a type", name)))
+        };
+        let values = match table.get("values") {
+            Some(toml::Value::Array(x)) => x.iter().map(|v| tomlScalarToString(v)).collect::<Option<Vec<String>>>(),
+            Some(_) => None,
+            None => Some(Vec::new())
+        };
+        let values = match values {
+            Some(x) => x,
+            None => return Err(Error::Validation(format!("The values of the custom field '{}' have to be a list", name)))
+        };
+
+        let default = match table.get("default") {
+            Some(x) => match tomlScalarToString(x) {
+                Some(x) => Some(x),
+                None => return Err(Error::Validation(format!("The default of the custom field '{}' has to be a single value", name)))
+            },
+            None => None
+        };
+
+        definitions.push(FieldDefinition::new(&name, stringToFieldType(&t, values)?, default.as_deref())?);
+    }
+
+    return Ok(definitions);
+}
+
+pub fn tomlScalarToString(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(x) => return Some(x.clone()),
+        toml::Value::Integer(x) => return Some(x.to_string()),
+        toml::Value::Float(x) => return Some(x.to_string()),
+        toml::Value::Boolean(x) => return Some(x.to_string()),
+        toml::Value::Datetime(x) => return Some(x.to_string()),
+        _ => return None
+    }
+}
+
+#[test]
+fn test_parse_field_definitions() {
+    use readinglist::fields::FieldType;
+
+    let doc: toml::Value = "
+        [[fields]]
+        name = \"pages\"
+        type = \"number\"
+        default = 100
+
+        [[fields]]
+        name = \"source\"
+        type = \"enum\"
+        values = [\"library\", \"friend\"]
+
+        [[fields]]
+        name = \"bought\"
+        type = \"date\"
+        default = 2024-01-05
+    ".parse().unwrap();
+
+    let fields = parseFieldDefinitions(&doc, &vec![String::from("title")]).unwrap();
+    assert_eq!(fields, vec![
+        FieldDefinition { name: String::from("pages"), t: FieldType::Number, default: Some(String::from("100")) },
+        FieldDefinition { name: String::from("source"), t: FieldType::Enum(vec![String::from("library"), String::from("friend")]), default: None },
+        FieldDefinition { name: String::from("bought"), t: FieldType::Date, default: Some(String::from("2024-01-05")) },
+    ]);
+
+    let taken: toml::Value = "[[fields]]\nname = \"title\"\ntype = \"text\"".parse().unwrap();
+    assert!(parseFieldDefinitions(&taken, &vec![String::from("title")]).is_err());
+
+    let unknownType: toml::Value = "[[fields]]\nname = \"pages\"\ntype = \"integer\"".parse().unwrap();
+    assert!(parseFieldDefinitions(&unknownType, &Vec::new()).is_err());
+
+    let empty: toml::Value = "".parse().unwrap();
+    assert!(parseFieldDefinitions(&empty, &Vec::new()).unwrap().is_empty());
+}
+
+#[test]
+fn test_fields_cannot_take_builtin_names() {
+    for name in vec!["id", "title", "created", "finished_at", "order"] {
+        let doc: toml::Value = format!("[[fields]]\nname = \"{}\"\ntype = \"number\"", name).parse().unwrap();
+        assert!(parseFieldDefinitions(&doc, &crate::reservedFieldNames()).is_err(), "{} should be rejected", name);
+    }
+}
+
+#[test]
+fn test_command_line_overrides_config_file() {
+    let known: Vec<String> = vec!["title", "author", "status", "tags", "created", "pages", "id"].iter().map(|x| x.to_string()).collect();
+    let file = Source::File(String::from("config.toml"));
+
+    let doc: toml::Value = "
+        db_file = \"~/books.db\"
+        query = \"status is reading\"
+        columns = [\"title\", \"pages\"]
+        sort = \"author\"
+        date_format = \"%d.%m.%Y\"
+
+        [colors]
+        header = \"cyan\"
+        reading = \"green\"
+
+        [templates]
+        html = \"~/wiki.html\"
+    ".parse().unwrap();
+
+    let mut config = Config::new(Vec::new());
+    config.applyConfigFile(&doc, "config.toml", &known).unwrap();
+    config.applyOverrides(Overrides {
+        query: Some(String::from("tags has rust")),
+        extraColumns: vec![String::from("title"), String::from("id")],
+        noColor: true,
+        ..Overrides::default()
+    }, &known).unwrap();
+
+    assert_eq!(config.db_file_location, "~/books.db");
+    assert_eq!(config.query, "tags has rust");
+    assert_eq!(config.columns, vec!["title", "pages", "id"]);
+    assert_eq!(config.sort, "author");
+    assert_eq!(config.date_format, "%d.%m.%Y");
+    assert_eq!(config.colors.header, Some(String::from("cyan")));
+    assert_eq!(config.colors.statuses.get("reading"), Some(&String::from("green")));
+    assert!(!config.colors.enabled);
+    assert_eq!(config.templates.get("html"), Some(&String::from("~/wiki.html")));
+
+    assert_eq!(config.sources["db_file"], file);
+    assert_eq!(config.sources["query"], Source::CommandLine);
+    assert_eq!(config.sources["columns"], Source::CommandLine);
+    assert_eq!(config.sources["ignore_case"], Source::Default);
+
+    let shown = config.show();
+    assert!(shown.lines().any(|l| l.starts_with("query = \"tags has rust\"") && l.ends_with("# command line")), "{}", shown);
+    assert!(shown.lines().any(|l| l.starts_with("colors.enabled = false") && l.ends_with("# command line")), "{}", shown);
+    assert!(shown.lines().any(|l| l.starts_with("colors.header = \"cyan\"") && l.ends_with("# config file config.toml")), "{}", shown);
+    assert!(shown.lines().any(|l| l.starts_with("db_file = \"~/books.db\"") && l.ends_with("# config file config.toml")), "{}", shown);
+    assert!(shown.lines().any(|l| l.starts_with("ignore_case = false") && l.ends_with("# default")), "{}", shown);
+
+    let invalid = vec![
+        "columns = [\"title\", \"isbn\"]",
+        "columns = []",
+        "date_format = \"%Y-%Q\"",
+        "ignore_case = \"yes\"",
+        "[colors]\nreading = \"pink\"",
+        "[colors]\nread = \"green\""
+    ];
+    for x in invalid {
+        let doc: toml::Value = x.parse().unwrap();
+        assert!(Config::new(Vec::new()).applyConfigFile(&doc, "config.toml", &known).is_err(), "{} should be rejected", x);
+    }
 }
@@ -0,0 +1,353 @@
+use std::collections::BTreeSet;
+
+use readinglist::{Backend, Error, ReadingEntry, Result, formatEnumToString, statusEnumToString};
+use readinglist::fields::FieldDefinition;
+
+use crate::config::tomlScalarToString;
+use crate::{formatTimestamp, setColumn};
+
+/// Marks the lines explaining why the last attempt was rejected. They are
+/// removed before the document is read.
+const ERROR_MARKER: &str = "# ERROR: ";
+
+const HEADER: &str = "# Change the items below and save to apply the changes. Items and fields
+# removed from the document stay as they are. Delete everything to cancel.
+";
+
+/// Renders the entries as a TOML document with an `[[items]]` table each
+pub fn toDocument(entries: &Vec<ReadingEntry>, fields: &Vec<FieldDefinition>, dateFormat: &str) -> String {
+    let quote = |x: &str| toml::Value::String(x.to_string()).to_string();
+
+    let mut doc = String::from(HEADER);
+    for re in entries {
+        doc.push_str("\n[[items]]\n");
+
+        // maintained by readinglist, so only shown
+        let timestamps: Vec<String> = vec![("created", &re.created_at), ("updated", &re.updated_at), ("started", &re.started_at), ("finished", &re.finished_at)]
+            .into_iter()
+            .filter(|(_, x)| x.is_some())
+            .map(|(name, x)| format!("{} {}", name, formatTimestamp(x, dateFormat)))
+            .collect();
+        if !timestamps.is_empty() {
+            doc.push_str(&format!("# {}\n", timestamps.join(", ")));
+        }
+
+        doc.push_str(&format!("id = {}\n", re.id));
+        doc.push_str(&format!("title = {}\n", quote(&re.title)));
+        doc.push_str(&format!("author = {}\n", quote(&re.author)));
+        doc.push_str(&format!("genre = {}\n", quote(&re.genre)));
+        doc.push_str(&format!("format = {}\n", quote(&formatEnumToString(&re.format))));
+        doc.push_str(&format!("status = {}\n", quote(&statusEnumToString(&re.status))));
+        doc.push_str(&format!("tags = [{}]\n", re.tags.iter().map(|t| quote(t)).collect::<Vec<String>>().join(", ")));
+        for def in fields {
+            doc.push_str(&format!("{} = {}\n", def.name, quote(re.fields.get(&def.name).map(|x| x.as_str()).unwrap_or(""))));
+        }
+    }
+
+    return doc;
+}
+
+/// Reads a document written by `toDocument` and returns the entries that
+/// changed. Fails without changing anything if any item is invalid.
+pub fn fromDocument(doc: &str, entries: &Vec<ReadingEntry>, fields: &Vec<FieldDefinition>) -> Result<Vec<ReadingEntry>> {
+    let value = match doc.parse::<toml::Value>() {
+        Ok(x) => x,
+        Err(e) => return Err(Error::Validation(format!("Invalid TOML: {}", e)))
+    };
+    let table = value.as_table().unwrap();
+    if let Some(key) = table.keys().find(|k| *k != "items") {
+        return Err(Error::Validation(format!("'{}' is neither an item nor a field of one", key)));
+    }
+    let items = match table.get("items") {
+        Some(toml::Value::Array(x)) => x.clone(),
+        Some(_) => return Err(Error::Validation(String::from("Items have to be [[items]] tables"))),
+        None => Vec::new()
+    };
+
+    let mut seen: BTreeSet<i64> = BTreeSet::new();
+    let mut changed: Vec<ReadingEntry> = Vec::new();
+    for item in &items {
+        let item = match item.as_table() {
+            Some(x) => x,
+            None => return Err(Error::Validation(String::from("Items have to be [[items]] tables")))
+        };
+        let id = match item.get("id").and_then(|x| x.as_integer()) {
+            Some(x) => x,
+            None => return Err(Error::Validation(String::from("Every item needs the id it had")))
+        };
+        if !seen.insert(id) {
+            return Err(Error::Validation(format!("Item {} appears more than once", id)));
+        }
+        let original = match entries.iter().find(|re| re.id == id) {
+            Some(x) => x,
+            None => return Err(Error::Validation(format!("Item {} isn't one of the items edited. Don't change the ids.", id)))
+        };
+
+        let mut re = original.clone();
+        for (key, value) in item {
+            if key == "id" {
+                continue;
+            }
+
+            // the array holds the tags already, splitting them again would break up tags with commas
+            if let ("tags", toml::Value::Array(tags)) = (key.as_str(), value) {
+                re.tags = match tags.iter().map(|t| tomlScalarToString(t)).collect::<Option<Vec<String>>>() {
+                    Some(x) => x,
+                    None => return Err(Error::Validation(format!("Item {}: '{}' has an invalid value", id, key)))
+                };
+                continue;
+            }
+
+            let text = match tomlScalarToString(value) {
+                Some(x) => x,
+                None => return Err(Error::Validation(format!("Item {}: '{}' has an invalid value", id, key)))
+            };
+            if let Err(e) = setColumn(&mut re, key, &text, fields) {
+                return Err(Error::Validation(format!("Item {}: {}", id, e)));
+            }
+        }
+
+        if !sameContent(&re, original) {
+            if let Some(tag) = re.tags.iter().find(|t| t.contains(',')) {
+                return Err(Error::Validation(format!("Item {}: The tag '{}' contains a comma, which separates tags", id, tag)));
+            }
+            changed.push(re);
+        }
+    }
+
+    return Ok(changed);
+}
+
+fn sameContent(a: &ReadingEntry, b: &ReadingEntry) -> bool {
+    // unset fields and empty ones are the same
+    let fieldsOf = |re: &ReadingEntry| -> Vec<(String, String)> {
+        re.fields.iter().filter(|(_, v)| !v.is_empty()).map(|(k, v)| (k.clone(), v.clone())).collect()
+    };
+    let tagsOf = |re: &ReadingEntry| -> Vec<String> {
+        re.tags.iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+    };
+
+    return a.title == b.title && a.author == b.author && a.genre == b.genre && a.format == b.format
+        && a.status == b.status && tagsOf(a) == tagsOf(b) && fieldsOf(a) == fieldsOf(b);
+}
+
+/// Removes the error comments of the last attempt
+fn stripErrors(doc: &str) -> String {
+    return doc.lines()
+        .filter(|line| !line.starts_with(ERROR_MARKER))
+        .map(|line| format!("{}\n", line))
+        .collect();
+}
+
+/// Lets the user edit the entries with `editor` until the document is valid
+/// or emptied, then stores all changes or, if one fails, none. An invalid
+/// document is handed back to `editor` with the reason on top. Returns the
+/// number of entries changed.
+pub fn editEntries(backend: &dyn Backend, entries: &Vec<ReadingEntry>, fields: &Vec<FieldDefinition>, dateFormat: &str,
+                   editor: &mut dyn FnMut(&str) -> Result<String>) -> Result<usize> {
+    let mut doc = toDocument(entries, fields, dateFormat);
+    loop {
+        let edited = stripErrors(&editor(&doc)?);
+        if edited.trim().is_empty() {
+            return Ok(0);
+        }
+
+        match fromDocument(&edited, entries, fields) {
+            Ok(changed) => {
+                // a document is saved as a whole, or not at all
+                backend.atomically(&mut || {
+                    for re in &changed {
+                        backend.updateEntry(re)?;
+                    }
+                    return Ok(());
+                })?;
+                return Ok(changed.len());
+            },
+            Err(e) => {
+                let reason: String = e.to_string().lines().map(|line| format!("{}{}\n", ERROR_MARKER, line)).collect();
+                doc = format!("{}{}", reason, edited);
+            }
+        }
+    }
+}
+
+/// A file in the temp directory only the user can read and write. It is
+/// removed when dropped, however editing ends.
+struct PrivateFile {
+    path: std::path::PathBuf
+}
+
+impl PrivateFile {
+    /// Never opens an existing file, so a file or symlink planted under the
+    /// same name isn't written to
+    fn create(contents: &str) -> Result<PrivateFile> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut attempts = 0;
+        loop {
+            let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|x| x.subsec_nanos()).unwrap_or(0);
+            let path = std::env::temp_dir().join(format!("rdnglst-edit-{}-{}.toml", std::process::id(), nanos));
+            match std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+                Ok(mut file) => {
+                    let private = PrivateFile { path: path };
+                    file.write_all(contents.as_bytes())?;
+                    return Ok(private);
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 10 => attempts += 1,
+                Err(e) => return Err(Error::from(e))
+            }
+        }
+    }
+}
+
+impl Drop for PrivateFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Opens the document in `$VISUAL` or `$EDITOR` and returns it as saved
+pub fn openInEditor(doc: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL").ok().filter(|x| !x.is_empty())
+        .or_else(|| std::env::var("EDITOR").ok().filter(|x| !x.is_empty()))
+        .unwrap_or_else(|| String::from("vi"));
+
+    let file = PrivateFile::create(doc)?;
+
+    // the editor may come with arguments, e.g. `code --wait`
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&file.path)
+        .status()?;
+    if !status.success() {
+        return Err(Error::Validation(format!("The editor '{}' failed. Nothing has been changed.", editor)));
+    }
+    return Ok(std::fs::read_to_string(&file.path)?);
+}
+
+#[test]
+fn test_invalid_documents_are_handed_back_with_the_reason() {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    let fields = vec![
+        FieldDefinition::new("pages", readinglist::fields::FieldType::Number, None).unwrap(),
+        FieldDefinition::new("source", readinglist::fields::FieldType::Enum(vec![String::from("library"), String::from("friend")]), None).unwrap(),
+        FieldDefinition::new("bought", readinglist::fields::FieldType::Date, None).unwrap(),
+        FieldDefinition::new("signed", readinglist::fields::FieldType::Bool, Some("no")).unwrap(),
+    ];
+    let entry = |title: &str, status: readinglist::Status, tags: Vec<&str>, values: Vec<(&str, &str)>| ReadingEntry {
+        id: -1,
+        title: title.to_string(),
+        author: String::from("Neil Gaiman"),
+        genre: String::new(),
+        format: readinglist::Format::Book,
+        status: status,
+        tags: tags.iter().map(|x| x.to_string()).collect(),
+        created_at: chrono::NaiveDateTime::parse_from_str("2024-01-02 12:00:00", "%Y-%m-%d %H:%M:%S").ok(),
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    };
+    backend.addEntry(&entry("Good Omens", readinglist::Status::Reading, vec!["humor", "myth"], vec![("pages", "412"), ("source", "friend"), ("signed", "false")])).unwrap();
+    backend.addEntry(&entry("Neverwhere", readinglist::Status::Wishlist, vec![], vec![("pages", "96")])).unwrap();
+    let entries = backend.getAllEntries().unwrap();
+
+    let doc = toDocument(&entries, &fields, "%Y-%m-%d");
+    assert!(doc.contains("[[items]]\n# created 2024-01-02, updated "), "{}", doc);
+    assert!(doc.contains("id = 1\ntitle = \"Good Omens\"\nauthor = \"Neil Gaiman\"\n"), "{}", doc);
+    assert!(doc.contains("tags = [\"humor\", \"myth\"]\npages = \"412\"\nsource = \"friend\"\nbought = \"\"\n"), "{}", doc);
+    assert!(fromDocument(&doc, &entries, &fields).unwrap().is_empty(), "Nothing changed");
+
+    // tags are taken from the array as they are
+    let mut legacy = entries.clone();
+    legacy[0].tags = vec![String::from("sci-fi, fantasy")];
+    assert!(fromDocument(&toDocument(&legacy, &fields, "%Y-%m-%d"), &legacy, &fields).unwrap().is_empty(), "A tag with a comma stays one tag");
+    let retagged = fromDocument(&doc.replace("tags = [\"humor\", \"myth\"]", "tags = [\"dark humor\", \"myth\", \"angels\"]"), &entries, &fields).unwrap();
+    assert_eq!(retagged[0].tags, vec!["dark humor", "myth", "angels"]);
+
+    let mut attempts: Vec<String> = Vec::new();
+    let mut editor = |doc: &str| -> Result<String> {
+        attempts.push(doc.to_string());
+        match attempts.len() {
+            // reading can't go back to the wishlist
+            1 => return Ok(doc.replace("status = \"reading\"", "status = \"wishlist\"").replace("pages = \"96\"", "pages = 128")),
+            2 => return Ok(doc.replacen("status = \"wishlist\"", "status = \"finished\"", 1).replace("bought = \"\"\nsigned = \"false\"", "bought = \"2024-05-01\"\nsigned = \"false\"")),
+            _ => return Ok(String::new())
+        }
+    };
+    let changed = editEntries(&backend, &entries, &fields, "%Y-%m-%d", &mut editor).unwrap();
+
+    assert_eq!(changed, 2);
+    assert_eq!(attempts.len(), 2);
+    assert!(attempts[1].starts_with("# ERROR: Item 1: "), "{}", attempts[1]);
+    assert_eq!(backend.getById(1).unwrap().status, readinglist::Status::Finished);
+    assert_eq!(backend.getById(1).unwrap().fields.get("bought"), Some(&String::from("2024-05-01")));
+    assert_eq!(backend.getById(2).unwrap().fields.get("pages"), Some(&String::from("128")));
+
+    let invalid = vec![
+        "[[items]]\ntitle = \"No id\"",
+        "[[items]]\nid = 3\ntitle = \"Not edited\"",
+        "[[items]]\nid = 1\n[[items]]\nid = 1",
+        "[[items]]\nid = 1\ncreated = \"2024-01-01\"",
+        "[[items]]\nid = 1\nisbn = \"123\"",
+        "[[items]]\nid = 1\nformat = \"scroll\"",
+        "[[items]]\nid = 1\ntags = [\"sci-fi, fantasy\"]",
+        "title = \"Outside\"",
+        "[[items]\nid = 1"
+    ];
+    for doc in invalid {
+        assert!(fromDocument(doc, &entries, &fields).is_err(), "{} should be rejected", doc);
+    }
+
+    // emptying the document cancels
+    assert_eq!(editEntries(&backend, &entries, &fields, "%Y-%m-%d", &mut |_: &str| Ok(String::new())).unwrap(), 0);
+}
+
+#[test]
+fn test_documents_are_saved_as_a_whole() {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    let re = ReadingEntry {
+        id: -1,
+        title: String::from("Mort"),
+        author: String::from("Terry Pratchett"),
+        genre: String::new(),
+        format: readinglist::Format::Book,
+        status: readinglist::Status::Wishlist,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: std::collections::BTreeMap::new()
+    };
+    backend.addEntry(&re).unwrap();
+    backend.addEntry(&re).unwrap();
+    let entries = backend.getAllEntries().unwrap();
+
+    // the second item is deleted while the document is open, so it can't be updated
+    let mut editor = |doc: &str| -> Result<String> {
+        backend.deleteById(2)?;
+        return Ok(doc.replace("title = \"Mort\"", "title = \"Reaper Man\""));
+    };
+    assert!(matches!(editEntries(&backend, &entries, &Vec::new(), "%Y-%m-%d", &mut editor), Err(Error::NotFound(2))));
+    assert_eq!(backend.getById(1).unwrap().title, "Mort", "The first item is left as it was");
+}
+
+#[test]
+fn test_documents_are_edited_in_a_private_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = PrivateFile::create("[[items]]\n").unwrap();
+    let path = file.path.clone();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[[items]]\n");
+
+    let other = PrivateFile::create("").unwrap();
+    assert_ne!(other.path, path, "Every document gets a file of its own");
+
+    drop(file);
+    assert!(!path.exists(), "The file is removed afterwards");
+}
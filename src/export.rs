@@ -0,0 +1,417 @@
+use readinglist::{Error, ReadingEntry, Result, Status, STATUSES, formatEnumToString, statusEnumToString};
+use readinglist::fields::FieldDefinition;
+
+use crate::{expandTilde, formatTimestamp};
+
+/// What `export` writes
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Document(DocumentFormat),
+    Bibtex,
+    CslJson
+}
+
+/// Documents to read, made from a template
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DocumentFormat {
+    Markdown,
+    Html,
+    Org
+}
+
+pub const EXPORT_FORMATS: [&str; 5] = ["markdown", "html", "org", "bibtex", "csl-json"];
+
+/// The formats rendered through a template, see `Config::templates`
+pub const TEMPLATE_FORMATS: [&str; 3] = ["markdown", "html", "org"];
+
+pub fn stringToExportFormat(name: &str) -> Result<ExportFormat> {
+    match name.trim().to_lowercase().as_str() {
+        "markdown" => return Ok(ExportFormat::Document(DocumentFormat::Markdown)),
+        "html" => return Ok(ExportFormat::Document(DocumentFormat::Html)),
+        "org" => return Ok(ExportFormat::Document(DocumentFormat::Org)),
+        "bibtex" => return Ok(ExportFormat::Bibtex),
+        "csl-json" => return Ok(ExportFormat::CslJson),
+        _ => return Err(Error::Validation(format!("Unknown export format '{}'. Use one of {}", name, EXPORT_FORMATS.join(", "))))
+    }
+}
+
+/// How `exportEntries` renders a document
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExportOptions {
+    pub format: DocumentFormat,
+    pub group_by: GroupBy,
+    pub title: String,
+    // when the document was exported, in the configured date format
+    pub date: String,
+    // see `loadTemplate`
+    pub template: String
+}
+
+/// How the entries of a document are split into sections
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GroupBy {
+    Status,
+    Genre,
+    Nothing
+}
+
+pub const GROUP_BY: [&str; 3] = ["status", "genre", "none"];
+
+pub fn stringToGroupBy(name: &str) -> Result<GroupBy> {
+    match name.trim().to_lowercase().as_str() {
+        "status" => return Ok(GroupBy::Status),
+        "genre" => return Ok(GroupBy::Genre),
+        "none" => return Ok(GroupBy::Nothing),
+        _ => return Err(Error::Validation(format!("Can't group by '{}'. Use one of {}", name, GROUP_BY.join(", "))))
+    }
+}
+
+/// What a template may contain. `{{items}}` is replaced by the rendered
+/// sections, the others by the values of the document.
+pub const PLACEHOLDERS: [&str; 4] = ["title", "date", "count", "items"];
+
+const MARKDOWN_TEMPLATE: &str = "# {{title}}
+
+{{count}} items, exported {{date}}.
+
+{{items}}
+";
+
+const HTML_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{{title}}</title>
+<style>
+body { font-family: sans-serif; max-width: 50em; margin: 2em auto; }
+.details { color: #666; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<p>{{count}} items, exported {{date}}.</p>
+{{items}}
+</body>
+</html>
+";
+
+const ORG_TEMPLATE: &str = "#+TITLE: {{title}}
+#+DATE: {{date}}
+
+{{count}} items.
+
+{{items}}
+";
+
+/// The template at `path`, or the builtin one of the format if there is none
+pub fn loadTemplate(format: DocumentFormat, path: Option<&String>) -> Result<String> {
+    let path = match path {
+        Some(x) => expandTilde(x),
+        None => match format {
+            DocumentFormat::Markdown => return Ok(MARKDOWN_TEMPLATE.to_string()),
+            DocumentFormat::Html => return Ok(HTML_TEMPLATE.to_string()),
+            DocumentFormat::Org => return Ok(ORG_TEMPLATE.to_string())
+        }
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(x) => return Ok(x),
+        Err(e) => return Err(Error::Validation(format!("Could not read the template {}: {}", path, e)))
+    }
+}
+
+/// Replaces the placeholders like `{{title}}`. Anything else in braces is an error.
+fn fillTemplate(template: &str, values: &Vec<(&str, String)>) -> Result<String> {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(x) => start + x,
+            None => return Err(Error::Validation(String::from("A placeholder of the template isn't closed with }}")))
+        };
+        let name = rest[start + 2..end].trim();
+        let value = match values.iter().find(|(n, _)| *n == name) {
+            Some((_, x)) => x,
+            None => return Err(Error::Validation(format!("Unknown placeholder '{{{{{}}}}}' in the template. Use one of {}", name, PLACEHOLDERS.join(", "))))
+        };
+
+        filled.push_str(&rest[..start]);
+        filled.push_str(value);
+        rest = &rest[end + 2..];
+    }
+    filled.push_str(rest);
+
+    return Ok(filled);
+}
+
+fn escapeMarkdown(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if "\\`*_[]<>|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    return escaped;
+}
+
+/// Org has no escapes, a zero width space in front keeps markup from taking
+/// effect: emphasis, links `[[`, description lists `::` and headings or
+/// keywords at the start of a line. Line breaks would end the item.
+fn escapeOrg(s: &str) -> String {
+    let mut escaped = String::new();
+    let mut previous: Option<char> = None;
+    for c in s.chars() {
+        let c = if c == '\n' || c == '\r' { ' ' } else { c };
+        let markup = "*/=~+_".contains(c)
+            || (c == '[' && previous == Some('['))
+            || (c == ':' && previous == Some(':'))
+            || (c == '#' && previous.is_none());
+        if markup {
+            escaped.push('\u{200b}');
+        }
+        escaped.push(c);
+        previous = Some(c);
+    }
+    return escaped;
+}
+
+pub fn escapeHtml(s: &str) -> String {
+    return s.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;");
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => return c.to_uppercase().chain(chars).collect(),
+        None => return String::new()
+    }
+}
+
+/// Splits the entries into titled sections, keeping their order within each.
+/// Statuses follow the lifecycle, genres the alphabet with the entries
+/// without one last.
+fn group(entries: &Vec<ReadingEntry>, groupBy: GroupBy) -> Vec<(Option<String>, Vec<&ReadingEntry>)> {
+    let keyOf = |re: &ReadingEntry| -> String {
+        match groupBy {
+            GroupBy::Status => return statusEnumToString(&re.status),
+            GroupBy::Genre => return re.genre.trim().to_lowercase(),
+            GroupBy::Nothing => return String::new()
+        }
+    };
+
+    let mut keys: Vec<String> = Vec::new();
+    for re in entries {
+        let key = keyOf(re);
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    match groupBy {
+        GroupBy::Status => keys.sort_by_key(|k| STATUSES.iter().position(|s| s == k)),
+        GroupBy::Genre => keys.sort_by_key(|k| (k.is_empty(), k.clone())),
+        GroupBy::Nothing => {}
+    }
+
+    return keys.iter()
+        .map(|key| {
+            let heading = match groupBy {
+                GroupBy::Nothing => None,
+                GroupBy::Genre if key.is_empty() => Some(String::from("No genre")),
+                _ => Some(capitalize(key))
+            };
+            return (heading, entries.iter().filter(|re| keyOf(re) == *key).collect());
+        })
+        .collect();
+}
+
+/// Everything but the title and author, leaving out what the section says already
+fn details(re: &ReadingEntry, groupBy: GroupBy, fields: &Vec<FieldDefinition>, dateFormat: &str) -> Vec<String> {
+    let mut details: Vec<String> = Vec::new();
+    if groupBy != GroupBy::Genre && !re.genre.trim().is_empty() {
+        details.push(re.genre.trim().to_string());
+    }
+    details.push(formatEnumToString(&re.format));
+    // the date tells already that it's finished
+    let finished = re.status == Status::Finished && re.finished_at.is_some();
+    if groupBy != GroupBy::Status && !finished {
+        details.push(statusEnumToString(&re.status));
+    }
+    if re.finished_at.is_some() {
+        details.push(format!("finished {}", formatTimestamp(&re.finished_at, dateFormat)));
+    }
+    if !re.tags.is_empty() {
+        details.push(format!("tags: {}", re.tags.join(", ")));
+    }
+    for def in fields {
+        if let Some(x) = re.fields.get(&def.name).filter(|x| !x.is_empty()) {
+            details.push(format!("{}: {}", def.name, x));
+        }
+    }
+
+    return details;
+}
+
+fn renderEntry(re: &ReadingEntry, format: DocumentFormat, details: &Vec<String>) -> String {
+    let author = re.author.trim();
+    match format {
+        DocumentFormat::Markdown => {
+            let mut line = format!("- **{}**", escapeMarkdown(&re.title));
+            if !author.is_empty() {
+                line.push_str(&format!(" by {}", escapeMarkdown(author)));
+            }
+            line.push_str(&format!(" — {}", escapeMarkdown(&details.join(", "))));
+            return line;
+        },
+        DocumentFormat::Html => {
+            let mut line = format!("<li><cite>{}</cite>", escapeHtml(&re.title));
+            if !author.is_empty() {
+                line.push_str(&format!(" by {}", escapeHtml(author)));
+            }
+            line.push_str(&format!(" <span class=\"details\">{}</span></li>", escapeHtml(&details.join(", "))));
+            return line;
+        },
+        DocumentFormat::Org => {
+            // emphasis can't contain its own marker
+            let title = escapeOrg(&re.title);
+            let mut line = if re.title.contains('/') { format!("- {}", title) } else { format!("- /{}/", title) };
+            if !author.is_empty() {
+                line.push_str(&format!(" by {}", escapeOrg(author)));
+            }
+            line.push_str(&format!(" — {}", escapeOrg(&details.join(", "))));
+            return line;
+        }
+    }
+}
+
+/// Renders the entries as a document made from the template of `options`, see `PLACEHOLDERS`
+pub fn exportEntries(entries: &Vec<ReadingEntry>, options: &ExportOptions, fields: &Vec<FieldDefinition>, dateFormat: &str) -> Result<String> {
+    let format = options.format;
+    let groupBy = options.group_by;
+    let mut sections: Vec<String> = Vec::new();
+    for (heading, members) in group(entries, groupBy) {
+        let mut lines: Vec<String> = Vec::new();
+        if let Some(heading) = heading {
+            let heading = format!("{} ({})", heading, members.len());
+            lines.push(match format {
+                DocumentFormat::Markdown => format!("## {}\n", escapeMarkdown(&heading)),
+                DocumentFormat::Html => format!("<h2>{}</h2>", escapeHtml(&heading)),
+                DocumentFormat::Org => format!("* {}", escapeOrg(&heading))
+            });
+        }
+        if format == DocumentFormat::Html {
+            lines.push(String::from("<ul>"));
+        }
+        for re in members {
+            lines.push(renderEntry(re, format, &details(re, groupBy, fields, dateFormat)));
+        }
+        if format == DocumentFormat::Html {
+            lines.push(String::from("</ul>"));
+        }
+        sections.push(lines.join("\n"));
+    }
+
+    let escape = |x: &str| match format {
+        DocumentFormat::Html => escapeHtml(x),
+        DocumentFormat::Org => escapeOrg(x),
+        DocumentFormat::Markdown => x.to_string()
+    };
+    let values = vec![
+        ("title", escape(&options.title)),
+        ("date", escape(&options.date)),
+        ("count", entries.len().to_string()),
+        ("items", sections.join("\n\n"))
+    ];
+
+    return fillTemplate(&options.template, &values);
+}
+
+#[test]
+fn test_documents_are_grouped_and_filled_into_the_template() {
+    use readinglist::fields::FieldType;
+
+    let fields = vec![
+        FieldDefinition::new("pages", FieldType::Number, None).unwrap(),
+        FieldDefinition::new("source", FieldType::Text, None).unwrap(),
+        FieldDefinition::new("signed", FieldType::Bool, Some("no")).unwrap(),
+    ];
+    let entry = |id: i64, title: &str, author: &str, genre: &str, format: &str, status: Status| ReadingEntry {
+        id: id,
+        title: title.to_string(),
+        author: author.to_string(),
+        genre: genre.to_string(),
+        format: readinglist::stringToFormatEnum(format).unwrap(),
+        status: status,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: std::collections::BTreeMap::new()
+    };
+    let mut goodOmens = entry(1, "Good Omens", "Neil Gaiman", "fantasy", "kindle", Status::Reading);
+    goodOmens.tags = vec![String::from("humor"), String::from("myth")];
+    goodOmens.fields = vec![("pages", "412"), ("source", "friend"), ("signed", "false")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    let mut sandman = entry(4, "The Sandman", "Neil Gaiman", "comic", "other:Graphic Novel", Status::Finished);
+    sandman.finished_at = chrono::NaiveDateTime::parse_from_str("2024-03-06 20:00:00", "%Y-%m-%d %H:%M:%S").ok();
+    let entries = vec![
+        goodOmens,
+        entry(2, "Mort", "Terry Pratchett", "Fantasy", "book", Status::Wishlist),
+        entry(3, "Ärger im Paradies", "Ödön Ümlaut", "", "book", Status::Wishlist),
+        sandman,
+    ];
+    let export = |format: DocumentFormat, groupBy: GroupBy, template: &str| {
+        let options = ExportOptions { format: format, group_by: groupBy, title: String::from("Our <list>"), date: String::from("2024-06-01"), template: template.to_string() };
+        return exportEntries(&entries, &options, &fields, "%Y-%m-%d");
+    };
+
+    let markdown = export(DocumentFormat::Markdown, GroupBy::Status, &loadTemplate(DocumentFormat::Markdown, None).unwrap()).unwrap();
+    assert!(markdown.starts_with("# Our <list>\n\n4 items, exported 2024-06-01.\n\n## Wishlist (2)\n\n- **Mort** by Terry Pratchett"), "{}", markdown);
+    let sections: Vec<usize> = ["## Wishlist", "## Reading", "## Finished"].iter().map(|h| markdown.find(h).unwrap()).collect();
+    assert!(sections[0] < sections[1] && sections[1] < sections[2], "{}", markdown);
+    assert!(!markdown.contains("## Queued"), "Empty sections are left out");
+
+    let html = export(DocumentFormat::Html, GroupBy::Genre, &loadTemplate(DocumentFormat::Html, None).unwrap()).unwrap();
+    assert!(html.contains("<title>Our &lt;list&gt;</title>"), "{}", html);
+    assert!(html.contains("<li><cite>Good Omens</cite> by Neil Gaiman <span class=\"details\">kindle, reading, tags: humor, myth, pages: 412, source: friend, signed: false</span></li>"), "{}", html);
+    assert!(html.contains("<h2>Fantasy (2)</h2>"), "Genres are grouped regardless of case");
+    assert!(html.find("<h2>Comic").unwrap() < html.find("<h2>No genre").unwrap(), "{}", html);
+    assert!(html.trim_end().ends_with("</ul>\n</body>\n</html>"), "{}", html);
+
+    let org = export(DocumentFormat::Org, GroupBy::Nothing, "{{ items }}").unwrap();
+    assert_eq!(org.lines().count(), 4);
+    assert!(org.starts_with("- /Good Omens/ by Neil Gaiman — "), "{}", org);
+    assert!(org.trim_end().ends_with("- /The Sandman/ by Neil Gaiman — comic, other:Graphic Novel, finished 2024-03-06"), "{}", org);
+
+    assert!(export(DocumentFormat::Org, GroupBy::Nothing, "{{author}}").is_err());
+    assert!(export(DocumentFormat::Org, GroupBy::Nothing, "{{title").is_err());
+    assert!(stringToExportFormat("pdf").is_err());
+    assert!(stringToGroupBy("author").is_err());
+}
+
+#[test]
+fn test_org_markup_in_items_is_escaped() {
+    let re = ReadingEntry {
+        id: 1,
+        title: String::from("* [[Links]] :: and =code="),
+        author: String::from("#+Someone"),
+        genre: String::from("*nix"),
+        format: readinglist::Format::Book,
+        status: Status::Wishlist,
+        tags: vec![String::from("a::b"), String::from("snake_case")],
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: std::collections::BTreeMap::new()
+    };
+    let options = ExportOptions { format: DocumentFormat::Org, group_by: GroupBy::Genre, title: String::from("*Mine*"), date: String::from("2024-06-01"), template: String::from("#+TITLE: {{title}}\n{{items}}") };
+    let org = exportEntries(&vec![re], &options, &Vec::new(), "%Y-%m-%d").unwrap();
+
+    assert_eq!(org.lines().count(), 3, "{}", org);
+    assert!(org.starts_with("#+TITLE: \u{200b}*Mine\u{200b}*\n* \u{200b}*nix (1)\n- /"), "{}", org);
+    for markup in vec!["[[", "::", " *", " =", "e_c"] {
+        assert!(!org.contains(markup), "{} in {}", markup, org);
+    }
+    assert_eq!(org.replace('\u{200b}', ""), "#+TITLE: *Mine*\n* *nix (1)\n- /* [[Links]] :: and =code=/ by #+Someone — book, wishlist, tags: a::b, snake_case");
+}
@@ -0,0 +1,129 @@
+use chrono::NaiveDate;
+
+use crate::{Error, Result};
+
+/// The kind of values a custom field holds
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldType {
+    Text,
+    Number,
+    Date,
+    Bool,
+    // one of the given values
+    Enum(Vec<String>)
+}
+
+/// The names of all field types as used in the config file
+pub const FIELD_TYPES: [&str; 5] = ["text", "number", "date", "bool", "enum"];
+
+/// The format date fields are stored and entered in
+pub const FIELD_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A field the user declared in addition to the builtin ones
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldDefinition {
+    pub name: String,
+    pub t: FieldType,
+    // in the canonical form `parse` produces
+    pub default: Option<String>
+}
+
+impl FieldDefinition {
+    /// Creates a definition after checking its name and default. Names are
+    /// used as columns in queries, so they are restricted to lowercase
+    /// letters, digits and underscores.
+    pub fn new(name: &str, t: FieldType, default: Option<&str>) -> Result<FieldDefinition> {
+        let validName = name.chars().next().map(|c| c.is_ascii_lowercase()).unwrap_or(false)
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !validName {
+            return Err(Error::Validation(format!("The field name '{}' must start with a lowercase letter and consist of lowercase letters, digits and '_' only", name)));
+        }
+
+        if let FieldType::Enum(values) = &t {
+            if values.is_empty() {
+                return Err(Error::Validation(format!("The enum field '{}' needs at least one value", name)));
+            }
+        }
+
+        let mut definition = FieldDefinition { name: name.to_string(), t: t, default: None };
+        if let Some(x) = default {
+            let parsed = definition.parse(x)?;
+            definition.default = if parsed.is_empty() { None } else { Some(parsed) };
+        }
+
+        return Ok(definition);
+    }
+
+    /// Checks a value entered by the user and brings it into its canonical
+    /// form, which is what gets stored. An empty value leaves the field unset.
+    pub fn parse(&self, value: &str) -> Result<String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+        if value.chars().any(|c| c.is_control()) {
+            return Err(Error::Validation(format!("The value of '{}' must not contain control characters", self.name)));
+        }
+
+        match &self.t {
+            FieldType::Text => return Ok(value.to_string()),
+            FieldType::Number => {
+                match value.parse::<f64>() {
+                    Ok(x) if x.is_finite() => return Ok(x.to_string()),
+                    _ => return Err(Error::Validation(format!("'{}' is not a number. The field '{}' expects one.", value, self.name)))
+                }
+            },
+            FieldType::Date => {
+                match NaiveDate::parse_from_str(value, FIELD_DATE_FORMAT) {
+                    Ok(x) => return Ok(x.format(FIELD_DATE_FORMAT).to_string()),
+                    Err(_) => return Err(Error::Validation(format!("'{}' is not a date (YYYY-MM-DD). The field '{}' expects one.", value, self.name)))
+                }
+            },
+            FieldType::Bool => {
+                match value.to_lowercase().as_str() {
+                    "true" | "yes" | "y" | "1" => return Ok(String::from("true")),
+                    "false" | "no" | "n" | "0" => return Ok(String::from("false")),
+                    _ => return Err(Error::Validation(format!("'{}' is neither true nor false. The field '{}' expects one of them.", value, self.name)))
+                }
+            },
+            FieldType::Enum(values) => {
+                match values.iter().find(|x| x.to_lowercase() == value.to_lowercase()) {
+                    Some(x) => return Ok(x.clone()),
+                    None => return Err(Error::Validation(format!("'{}' is not allowed for the field '{}'. Use one of {}", value, self.name, values.join(", "))))
+                }
+            }
+        }
+    }
+}
+
+pub fn stringToFieldType(name: &str, values: Vec<String>) -> Result<FieldType> {
+    match name.trim().to_lowercase().as_str() {
+        "text" => return Ok(FieldType::Text),
+        "number" => return Ok(FieldType::Number),
+        "date" => return Ok(FieldType::Date),
+        "bool" => return Ok(FieldType::Bool),
+        "enum" => return Ok(FieldType::Enum(values)),
+        _ => return Err(Error::Validation(format!("Unknown field type '{}'. Use one of {}", name, FIELD_TYPES.join(", "))))
+    }
+}
+
+#[test]
+fn test_values_are_brought_into_canonical_form() {
+    let field = |t: FieldType| FieldDefinition::new("f", t, None).unwrap();
+
+    assert_eq!(field(FieldType::Text).parse("  some text ").unwrap(), "some text");
+    assert_eq!(field(FieldType::Number).parse("300.0").unwrap(), "300");
+    assert_eq!(field(FieldType::Number).parse("4.5").unwrap(), "4.5");
+    assert!(field(FieldType::Number).parse("many").is_err());
+    assert_eq!(field(FieldType::Date).parse("2024-1-5").unwrap(), "2024-01-05");
+    assert!(field(FieldType::Date).parse("05.01.2024").is_err());
+    assert_eq!(field(FieldType::Bool).parse("Yes").unwrap(), "true");
+    assert_eq!(field(FieldType::Enum(vec![String::from("Library"), String::from("Friend")])).parse("library").unwrap(), "Library");
+    assert!(field(FieldType::Enum(vec![String::from("Library")])).parse("Shop").is_err());
+    assert_eq!(field(FieldType::Number).parse(" ").unwrap(), "");
+
+    assert!(FieldDefinition::new("Pages", FieldType::Number, None).is_err());
+    assert!(FieldDefinition::new("1st", FieldType::Number, None).is_err());
+    assert!(FieldDefinition::new("pages", FieldType::Number, Some("lots")).is_err());
+    assert_eq!(FieldDefinition::new("pages", FieldType::Number, Some("100")).unwrap().default, Some(String::from("100")));
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use readinglist::{Error, Format, ReadingEntry, Result, Status};
+use readinglist::fields::FieldDefinition;
+
+use crate::import::applyDefaults;
+
+/// Shelves every Goodreads account has. Only used for the status, never as tags.
+const BUILTIN_SHELVES: [&str; 3] = ["read", "currently-reading", "to-read"];
+
+/// Splits CSV as written by Goodreads and spreadsheets into rows of fields.
+/// Quoted fields may contain commas, line breaks and doubled quotes.
+pub fn parseCsv(text: &str) -> Result<Vec<Vec<String>>> {
+    let text = text.trim_start_matches('\u{feff}');
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); },
+                '"' => quoted = false,
+                _ => field.push(c)
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            _ => field.push(c)
+        }
+    }
+    if quoted {
+        return Err(Error::Validation(format!("A quoted field isn't closed before the end of the file (line {})", line)));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    // blank lines
+    rows.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    return Ok(rows);
+}
+
+/// Goodreads writes ISBNs as `="0345391802"` so spreadsheets keep them text
+fn cleanIsbn(isbn: &str) -> String {
+    return isbn.trim().trim_start_matches('=').trim_matches('"').trim().to_string();
+}
+
+/// Dates look like `2024/03/01`
+fn parseDate(date: &str) -> Option<NaiveDateTime> {
+    let date = date.trim();
+    return NaiveDate::parse_from_str(date, "%Y/%m/%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .ok()
+        .map(|x| x.and_hms_opt(0, 0, 0).unwrap());
+}
+
+fn shelfToStatus(shelf: &str) -> Option<Status> {
+    match shelf {
+        "read" => return Some(Status::Finished),
+        "currently-reading" => return Some(Status::Reading),
+        "to-read" => return Some(Status::Wishlist),
+        "did-not-finish" | "dnf" | "abandoned" => return Some(Status::Abandoned),
+        "on-hold" | "paused" => return Some(Status::Paused),
+        _ => return None
+    }
+}
+
+/// Maps the rows of a Goodreads export onto entries. Fails without returning
+/// any entry if a row is invalid.
+pub fn fromGoodreadsCsv(text: &str, fields: &Vec<FieldDefinition>) -> Result<Vec<ReadingEntry>> {
+    let rows = parseCsv(text)?;
+    let header = match rows.first() {
+        Some(x) => x.iter().map(|x| x.trim().to_string()).collect::<Vec<String>>(),
+        None => return Ok(Vec::new())
+    };
+    for required in ["Title", "Author", "Exclusive Shelf"].iter() {
+        if !header.iter().any(|x| x == required) {
+            return Err(Error::Validation(format!("The column '{}' is missing. Is this a Goodreads export?", required)));
+        }
+    }
+
+    let mut entries: Vec<ReadingEntry> = Vec::new();
+    for (i, row) in rows.iter().enumerate().skip(1) {
+        let invalid = |reason: String| Error::Validation(format!("Row {}: {}", i + 1, reason));
+        let get = |name: &str| -> String {
+            return header.iter().position(|x| x == name).and_then(|i| row.get(i)).map(|x| x.trim().to_string()).unwrap_or_default();
+        };
+
+        let title = get("Title");
+        if title.is_empty() {
+            return Err(invalid(String::from("The title is missing")));
+        }
+
+        let shelf = get("Exclusive Shelf").to_lowercase();
+        let mut tags: Vec<String> = Vec::new();
+        let status = match shelfToStatus(&shelf) {
+            Some(x) => x,
+            None => {
+                // a shelf of its own, so it's kept as tag
+                tags.push(shelf.clone());
+                Status::Wishlist
+            }
+        };
+        for x in get("Bookshelves").split(',').map(|x| x.trim().to_lowercase()) {
+            if !x.is_empty() && x != shelf && !BUILTIN_SHELVES.contains(&x.as_str()) && !tags.contains(&x) {
+                tags.push(x);
+            }
+        }
+
+        let dateOf = |name: &str| -> Result<Option<NaiveDateTime>> {
+            let value = get(name);
+            if value.is_empty() {
+                return Ok(None);
+            }
+            return parseDate(&value).map(Some).ok_or_else(|| invalid(format!("'{}' isn't a valid {}", value, name)));
+        };
+        let finished = if status == Status::Finished { dateOf("Date Read")? } else { None };
+
+        let mut values: BTreeMap<String, String> = BTreeMap::new();
+        let isbn = Some(cleanIsbn(&get("ISBN13"))).filter(|x| !x.is_empty()).unwrap_or_else(|| cleanIsbn(&get("ISBN")));
+        if !isbn.is_empty() {
+            values.insert(String::from("isbn"), isbn);
+        }
+        let rating = get("My Rating");
+        if !rating.is_empty() && rating != "0" {
+            values.insert(String::from("rating"), rating);
+        }
+        // declared fields have to hold valid values, others are stored as they are
+        for (name, value) in values.iter_mut() {
+            if let Some(def) = fields.iter().find(|def| def.name == *name) {
+                *value = def.parse(value).map_err(|e| invalid(e.to_string()))?;
+            }
+        }
+
+        let mut re = ReadingEntry {
+            id: -1,
+            title: title,
+            author: get("Author"),
+            genre: String::new(),
+            format: Format::Book,
+            status: status,
+            tags: tags,
+            created_at: dateOf("Date Added")?,
+            updated_at: None,
+            started_at: None,
+            finished_at: finished,
+            fields: values
+        };
+        applyDefaults(&mut re, fields);
+        entries.push(re);
+    }
+
+    return Ok(entries);
+}
+
+#[test]
+fn test_goodreads_exports_are_mapped_to_entries() {
+    use readinglist::fields::FieldType;
+
+    let csv = "\u{feff}Book Id,Title,Author,Author l-f,ISBN,ISBN13,My Rating,Exclusive Shelf,Bookshelves,Date Read,Date Added\r
+1,\"Mort (Discworld, #4)\",Terry Pratchett,\"Pratchett, Terry\",\"=\"\"0552131067\"\"\",\"=\"\"\"\"\",4,read,\"fantasy, read, humor\",2024/03/01,2023/12/24\r
+2,\"Say \"\"Hi\"\"\nTwice\",Someone,,,=\"9780000000002\",0,currently-reading,currently-reading,,2024/01/05\r
+\r
+3,Antifragile,Nassim Taleb,,,,0,owned-books,\"owned-books, essay\",,2024/01/06\r
+";
+    let fields = vec![FieldDefinition::new("rating", FieldType::Number, None).unwrap(), FieldDefinition::new("source", FieldType::Text, Some("goodreads")).unwrap()];
+    let entries = fromGoodreadsCsv(csv, &fields).unwrap();
+    assert_eq!(entries.len(), 3);
+
+    let mort = &entries[0];
+    assert_eq!((mort.title.as_str(), mort.author.as_str(), &mort.status), ("Mort (Discworld, #4)", "Terry Pratchett", &Status::Finished));
+    assert_eq!(mort.tags, vec!["fantasy", "humor"]);
+    assert_eq!(mort.finished_at, Some(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()));
+    assert_eq!(mort.created_at, Some(NaiveDate::from_ymd_opt(2023, 12, 24).unwrap().and_hms_opt(0, 0, 0).unwrap()));
+    assert_eq!(mort.fields.get("isbn"), Some(&String::from("0552131067")));
+    assert_eq!(mort.fields.get("rating"), Some(&String::from("4")));
+    assert_eq!(mort.fields.get("source"), Some(&String::from("goodreads")));
+
+    assert_eq!(entries[1].title, "Say \"Hi\"\nTwice");
+    assert_eq!(entries[1].status, Status::Reading);
+    assert!(entries[1].tags.is_empty());
+    assert_eq!(entries[1].fields.get("isbn"), Some(&String::from("9780000000002")));
+    assert_eq!(entries[1].fields.get("rating"), None);
+
+    assert_eq!(entries[2].status, Status::Wishlist);
+    assert_eq!(entries[2].tags, vec!["owned-books", "essay"]);
+
+    let invalid = vec![
+        "Title,Author\nMort,Terry Pratchett",
+        "Title,Author,Exclusive Shelf\n,Terry Pratchett,read",
+        "Title,Author,Exclusive Shelf,Date Read\nMort,Terry Pratchett,read,yesterday",
+        "Title,Author,Exclusive Shelf,My Rating\nMort,Terry Pratchett,read,five",
+        "Title,Author,Exclusive Shelf\n\"Mort,Terry Pratchett,read"
+    ];
+    for csv in invalid {
+        assert!(fromGoodreadsCsv(csv, &fields).is_err(), "{} should be rejected", csv);
+    }
+}
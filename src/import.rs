@@ -0,0 +1,188 @@
+use readinglist::{Backend, Error, ReadingEntry, Result, statusEnumToString};
+use readinglist::fields::FieldDefinition;
+
+use regex::Regex;
+
+/// What importing an entry did, or would do on a dry run
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    // the id of the new entry, None on a dry run
+    Added(Option<i64>),
+    // the id of the entry it duplicates. None if that's one added by the
+    // same import on a dry run.
+    Duplicate(Option<i64>)
+}
+
+lazy_static! {
+    // Goodreads and others add the series to the title, e.g. `Mort (Discworld, #4)`
+    static ref SERIES: Regex = Regex::new(r"\s*\([^()]*#\d+(\.\d+)?\)\s*$").unwrap();
+}
+
+/// Lowercase words without punctuation and series, to compare titles and authors
+pub fn matchKey(s: &str) -> String {
+    let s = SERIES.replace(s, "").to_lowercase();
+    let words: Vec<&str> = s.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    return words.join(" ");
+}
+
+/// Whether two author lists have an author in common, e.g. `Neil Gaiman`
+/// and `Terry Pratchett; Neil Gaiman`
+pub fn sameAuthors(a: &str, b: &str) -> bool {
+    let names = |s: &str| -> Vec<String> {
+        return s.split(|c| c == ';' || c == ',' || c == '&').flat_map(|x| x.split(" and ")).map(|x| matchKey(x)).collect();
+    };
+    if matchKey(a) == matchKey(b) {
+        return true;
+    }
+    let other = names(b);
+    return names(a).iter().any(|x| !x.is_empty() && other.contains(x));
+}
+
+/// Finds the entry with the same title by one of the same authors
+pub fn findEntry<'a>(entries: &'a Vec<ReadingEntry>, title: &str, author: &str) -> Option<&'a ReadingEntry> {
+    return entries.iter().find(|re| matchKey(&re.title) == matchKey(title) && sameAuthors(&re.author, author));
+}
+
+/// Fields identifying what an entry is. The first both entries have decides
+/// whether they are the same.
+const IDENTIFIERS: [&str; 3] = ["isbn", "doi", "citekey"];
+
+fn isDuplicate(a: &ReadingEntry, b: &ReadingEntry) -> bool {
+    for name in IDENTIFIERS.iter() {
+        let identifier = |re: &ReadingEntry| re.fields.get(*name).map(|x| x.replace("-", "").to_lowercase()).filter(|x| !x.is_empty());
+        if let (Some(x), Some(y)) = (identifier(a), identifier(b)) {
+            return x == y;
+        }
+    }
+    return matchKey(&a.title) == matchKey(&b.title) && sameAuthors(&a.author, &b.author);
+}
+
+/// Default values of the custom fields the entry has no value for
+pub fn applyDefaults(re: &mut ReadingEntry, fields: &Vec<FieldDefinition>) {
+    for def in fields {
+        if let Some(x) = &def.default {
+            re.fields.entry(def.name.clone()).or_insert_with(|| x.clone());
+        }
+    }
+}
+
+/// Adds the entries that aren't in the list yet, matched by `IDENTIFIERS` or
+/// else by title and one of the authors. A dry run only finds out what would be added.
+/// Either all entries are added or, if one is rejected, none.
+pub fn importEntries(backend: &dyn Backend, entries: Vec<ReadingEntry>, dryRun: bool) -> Result<Vec<(ReadingEntry, Outcome)>> {
+    let mut known = backend.getAllEntries()?;
+    let mut results = Vec::new();
+    backend.atomically(&mut || {
+        for re in &entries {
+            if let Some(other) = known.iter().find(|x| isDuplicate(x, re)) {
+                let id = if other.id > 0 { Some(other.id) } else { None };
+                results.push((re.clone(), Outcome::Duplicate(id)));
+                continue;
+            }
+
+            let mut added = re.clone();
+            added.id = if dryRun { -1 } else { backend.addEntry(re).map_err(|e| describeFailure(re, e))? };
+            let id = if dryRun { None } else { Some(added.id) };
+            known.push(added);
+            results.push((re.clone(), Outcome::Added(id)));
+        }
+        return Ok(());
+    })?;
+
+    return Ok(results);
+}
+
+/// Names the entry that was rejected, as nothing has been imported then
+fn describeFailure(re: &ReadingEntry, e: Error) -> Error {
+    match e {
+        Error::Validation(reason) => return Error::Validation(format!("'{}': {}. Nothing has been imported.", re.title, reason)),
+        _ => return e
+    }
+}
+
+/// A line per entry and the totals
+pub fn report(results: &Vec<(ReadingEntry, Outcome)>, dryRun: bool) -> String {
+    let describe = |re: &ReadingEntry| {
+        if re.author.is_empty() {
+            return format!("'{}'", re.title);
+        }
+        return format!("'{}' by {}", re.title, re.author);
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    for (re, outcome) in results {
+        lines.push(match outcome {
+            Outcome::Added(Some(id)) => format!("Added {} as item {} ({})", describe(re), id, statusEnumToString(&re.status)),
+            Outcome::Added(None) => format!("Would add {} ({})", describe(re), statusEnumToString(&re.status)),
+            Outcome::Duplicate(Some(id)) => format!("Skipped {}, it is item {} already", describe(re), id),
+            Outcome::Duplicate(None) => format!("Skipped {}, it appears before", describe(re))
+        });
+    }
+
+    let added = results.iter().filter(|(_, o)| matches!(o, Outcome::Added(_))).count();
+    let skipped = results.len() - added;
+    if dryRun {
+        lines.push(format!("Would add {} items and skip {} duplicates. Nothing has been changed.", added, skipped));
+    } else {
+        lines.push(format!("Added {} items, skipped {} duplicates", added, skipped));
+    }
+
+    return lines.join("\n");
+}
+
+#[test]
+fn test_duplicates_are_skipped() {
+    use readinglist::Backend;
+
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    let entry = |title: &str, author: &str, isbn: Option<&str>| {
+        let mut re = ReadingEntry {
+            id: -1,
+            title: title.to_string(),
+            author: author.to_string(),
+            genre: String::new(),
+            format: readinglist::Format::Book,
+            status: readinglist::Status::Wishlist,
+            tags: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            started_at: None,
+            finished_at: None,
+            fields: std::collections::BTreeMap::new()
+        };
+        if let Some(x) = isbn {
+            re.fields.insert(String::from("isbn"), x.to_string());
+        }
+        return re;
+    };
+    let titles = || -> Vec<String> { backend.getAllEntries().unwrap().into_iter().map(|re| re.title).collect() };
+    backend.addEntry(&entry("Mort", "Terry Pratchett", None)).unwrap();
+
+    let entries = vec![
+        entry("Mort (Discworld, #4)", "Terry  Pratchett", None),
+        entry("Small Gods", "Terry Pratchett", Some("978-0552152976")),
+        entry("Small Gods (Discworld #13)", "T. Pratchett", Some("9780552152976")),
+        entry("The Sandman: Preludes & Nocturnes", "Neil Gaiman", None)
+    ];
+
+    let dryRun = importEntries(&backend, entries.clone(), true).unwrap();
+    let outcomes: Vec<&Outcome> = dryRun.iter().map(|(_, o)| o).collect();
+    assert_eq!(outcomes, vec![&Outcome::Duplicate(Some(1)), &Outcome::Added(None), &Outcome::Duplicate(None), &Outcome::Added(None)]);
+    assert_eq!(titles(), vec!["Mort"], "A dry run stores nothing");
+    assert!(report(&dryRun, true).ends_with("Would add 2 items and skip 2 duplicates. Nothing has been changed."));
+
+    let imported = importEntries(&backend, entries.clone(), false).unwrap();
+    assert_eq!(imported[1].1, Outcome::Added(Some(2)));
+    assert_eq!(imported[2].1, Outcome::Duplicate(Some(2)));
+    assert_eq!(titles(), vec!["Mort", "Small Gods", "The Sandman: Preludes & Nocturnes"]);
+
+    // importing again changes nothing
+    assert!(importEntries(&backend, entries.clone(), false).unwrap().iter().all(|(_, o)| matches!(o, Outcome::Duplicate(Some(_)))));
+
+    // a rejected entry leaves the list as it was
+    let mut rejected = vec![entry("Reaper Man", "Terry Pratchett", None), entry("Guards! Guards!", "Terry Pratchett", None)];
+    rejected[1].tags = vec![String::from("city, watch")];
+    let error = importEntries(&backend, rejected, false).unwrap_err();
+    assert!(error.to_string().contains("'Guards! Guards!'"), "{}", error);
+    assert!(!titles().contains(&String::from("Reaper Man")), "Nothing is imported");
+}
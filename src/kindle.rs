@@ -0,0 +1,323 @@
+use chrono::{Local, NaiveDateTime, TimeZone};
+use regex::Regex;
+
+use readinglist::{Backend, Clipping, ClippingKind, Error, Format, ReadingEntry, Result, Status};
+use readinglist::fields::FieldDefinition;
+
+use crate::import::{applyDefaults, findEntry};
+
+/// Ends every clipping in `My Clippings.txt`
+const SEPARATOR: &str = "==========";
+
+lazy_static! {
+    // `Title (Author)`, the title may contain parentheses itself
+    static ref TITLE_LINE: Regex = Regex::new(r"^(.*?)\s*\(([^()]*)\)\s*$").unwrap();
+    static ref LOCATION: Regex = Regex::new(r"(?i)\blocation\s+(\d+(-\d+)?)").unwrap();
+    static ref PAGE: Regex = Regex::new(r"(?i)\bpage\s+([0-9ivxlcdm]+(-[0-9ivxlcdm]+)?)").unwrap();
+}
+
+/// How Kindles write when a clipping was added, depending on the region
+const ADDED_FORMATS: [&str; 3] = ["%A, %d %B %Y %H:%M:%S", "%A, %B %d, %Y %I:%M:%S %p", "%A, %B %d, %Y %H:%M:%S"];
+
+/// A clipping and the book it was made in
+#[derive(Debug, PartialEq, Clone)]
+pub struct KindleClipping {
+    pub title: String,
+    pub author: String,
+    pub clipping: Clipping
+}
+
+/// Authors are written like `Pratchett, Terry` or `Gaiman, Neil;Pratchett, Terry`
+fn normalizeAuthors(authors: &str) -> String {
+    let names: Vec<String> = authors.split(';')
+        .map(|name| {
+            let parts: Vec<&str> = name.split(',').map(|x| x.trim()).collect();
+            if parts.len() == 2 && !parts[1].is_empty() {
+                return format!("{} {}", parts[1], parts[0]);
+            }
+            return name.trim().to_string();
+        })
+        .filter(|x| !x.is_empty())
+        .collect();
+    return names.join(", ");
+}
+
+/// Kindles write local time, the backend keeps UTC
+fn parseAdded(meta: &str) -> Option<NaiveDateTime> {
+    let added = meta.splitn(2, "Added on").nth(1)?.trim();
+    let local = ADDED_FORMATS.iter().find_map(|f| NaiveDateTime::parse_from_str(added, f).ok())?;
+    return Local.from_local_datetime(&local).earliest().map(|x| x.naive_utc());
+}
+
+/// Reads the highlights and notes of `My Clippings.txt`. Bookmarks and empty
+/// highlights are left out.
+pub fn parseClippings(text: &str) -> Result<Vec<KindleClipping>> {
+    let mut clippings = Vec::new();
+    for (i, record) in text.split(SEPARATOR).enumerate() {
+        let invalid = |reason: &str| Error::Validation(format!("Clipping {}: {}", i + 1, reason));
+
+        // every record starts with a byte order mark, not just the file
+        let lines: Vec<&str> = record.lines().map(|x| x.trim_start_matches('\u{feff}').trim_end()).collect();
+        let lines: Vec<&str> = lines.into_iter().skip_while(|x| x.trim().is_empty()).collect();
+        if lines.is_empty() {
+            continue;
+        }
+
+        let (title, author) = match TITLE_LINE.captures(lines[0].trim()) {
+            Some(c) => (c[1].to_string(), normalizeAuthors(&c[2])),
+            None => (lines[0].trim().to_string(), String::new())
+        };
+        let meta = match lines.get(1) {
+            Some(x) if x.trim_start().starts_with("- ") => x.trim(),
+            _ => return Err(invalid("The line telling what kind of clipping it is is missing"))
+        };
+
+        let kind = if meta.contains("Highlight") {
+            ClippingKind::Highlight
+        } else if meta.contains("Note") {
+            ClippingKind::Note
+        } else if meta.contains("Bookmark") {
+            continue;
+        } else {
+            return Err(invalid(&format!("Unknown kind of clipping '{}'", meta)));
+        };
+
+        let content = lines[2..].join("\n").trim().to_string();
+        if content.is_empty() {
+            continue;
+        }
+
+        clippings.push(KindleClipping {
+            title: title,
+            author: author,
+            clipping: Clipping {
+                entry_id: -1,
+                kind: kind,
+                location: LOCATION.captures(meta).map(|c| c[1].to_string()).unwrap_or_default(),
+                page: PAGE.captures(meta).map(|c| c[1].to_string()).unwrap_or_default(),
+                added_at: parseAdded(meta),
+                content: content
+            }
+        });
+    }
+
+    return Ok(clippings);
+}
+
+/// What importing the clippings of a book did, or would do on a dry run
+#[derive(Debug, PartialEq)]
+pub struct BookImport {
+    pub title: String,
+    pub author: String,
+    // the id of the entry, None if it would be created on a dry run
+    pub id: Option<i64>,
+    pub created: bool,
+    pub added: usize,
+    // imported before
+    pub known: usize
+}
+
+/// Stores the clippings with the entries of their books. Books not in the list
+/// are added as Kindle books being read. Clippings imported before are left out.
+/// Either everything is stored or, if anything fails, nothing.
+pub fn importClippings(backend: &dyn Backend, clippings: &Vec<KindleClipping>, fields: &Vec<FieldDefinition>, dryRun: bool) -> Result<Vec<BookImport>> {
+    let mut entries = backend.getAllEntries()?;
+    let mut results: Vec<BookImport> = Vec::new();
+    // what a dry run would have stored, with the index of the book
+    let mut pending: Vec<(usize, Clipping)> = Vec::new();
+
+    backend.atomically(&mut || {
+        for kc in clippings {
+            let index = match results.iter().position(|b| b.title == kc.title && b.author == kc.author) {
+                Some(x) => x,
+                None => {
+                    let (id, created) = match findEntry(&entries, &kc.title, &kc.author) {
+                        Some(re) => (Some(re.id), false),
+                        None => {
+                            let mut re = ReadingEntry {
+                                id: -1,
+                                title: kc.title.clone(),
+                                author: kc.author.clone(),
+                                genre: String::new(),
+                                format: Format::Kindle,
+                                status: Status::Reading,
+                                tags: Vec::new(),
+                                created_at: None,
+                                updated_at: None,
+                                started_at: kc.clipping.added_at,
+                                finished_at: None,
+                                fields: std::collections::BTreeMap::new()
+                            };
+                            applyDefaults(&mut re, fields);
+                            if !dryRun {
+                                re.id = backend.addEntry(&re)?;
+                            }
+                            let id = if dryRun { None } else { Some(re.id) };
+                            entries.push(re);
+                            (id, true)
+                        }
+                    };
+                    results.push(BookImport { title: kc.title.clone(), author: kc.author.clone(), id: id, created: created, added: 0, known: 0 });
+                    results.len() - 1
+                }
+            };
+
+            let book = &mut results[index];
+            let clipping = Clipping { entry_id: book.id.unwrap_or(-1), ..kc.clipping.clone() };
+            let isNew = if dryRun {
+                // nothing is stored, so repetitions within the file are found here
+                let same = |c: &Clipping| c.kind == clipping.kind && c.location == clipping.location && c.content == clipping.content;
+                let stored = match book.id {
+                    Some(id) => backend.getClippings(id)?,
+                    None => Vec::new()
+                };
+                !stored.iter().any(same) && !pending.iter().any(|(i, c)| *i == index && same(c))
+            } else {
+                backend.addClipping(&clipping)?
+            };
+            if isNew {
+                book.added += 1;
+                if dryRun {
+                    pending.push((index, clipping));
+                }
+            } else {
+                book.known += 1;
+            }
+        }
+        return Ok(());
+    })?;
+
+    return Ok(results);
+}
+
+/// A line per book and the totals
+pub fn report(results: &Vec<BookImport>, dryRun: bool) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for book in results {
+        let name = if book.author.is_empty() { format!("'{}'", book.title) } else { format!("'{}' by {}", book.title, book.author) };
+        let item = match (book.id, book.created) {
+            (Some(id), true) => format!("new item {}", id),
+            (Some(id), false) => format!("item {}", id),
+            (None, _) => String::from("a new item")
+        };
+        lines.push(format!("{} ({}): {} new, {} imported before", name, item, book.added, book.known));
+    }
+
+    let created = results.iter().filter(|b| b.created).count();
+    let added: usize = results.iter().map(|b| b.added).sum();
+    let known: usize = results.iter().map(|b| b.known).sum();
+    if dryRun {
+        lines.push(format!("Would add {} items and {} highlights and notes, {} were imported before. Nothing has been changed.", created, added, known));
+    } else {
+        lines.push(format!("Added {} items and {} highlights and notes, {} were imported before", created, added, known));
+    }
+
+    return lines.join("\n");
+}
+
+#[cfg(test)]
+const CLIPPINGS: &str = "\u{feff}Good Omens (Gaiman, Neil;Pratchett, Terry)
+- Your Highlight on page 12 | Location 170-172 | Added on Sunday, 3 March 2024 10:15:30
+
+It wasn't a dark and stormy night.
+==========
+\u{feff}Good Omens (Gaiman, Neil;Pratchett, Terry)
+- Your Bookmark on Location 200 | Added on Sunday, 3 March 2024 10:16:00
+
+
+==========
+\u{feff}Good Omens (Gaiman, Neil;Pratchett, Terry)
+- Your Note on Location 172 | Added on Sunday, 3 March 2024 10:17:00
+
+Crowley!
+Twice.
+==========
+\u{feff}The Black Swan (Second Edition) (Nassim Nicholas Taleb)
+- Your Highlight at location 1020-1022 | Added on Monday, March 4, 2024 9:05:01 PM
+
+Absence of evidence is not evidence of absence.
+==========
+";
+
+#[test]
+fn test_clippings_are_parsed() {
+    let clippings = parseClippings(CLIPPINGS).unwrap();
+    assert_eq!(clippings.len(), 3, "Bookmarks are left out");
+
+    assert_eq!((clippings[0].title.as_str(), clippings[0].author.as_str()), ("Good Omens", "Neil Gaiman, Terry Pratchett"));
+    assert_eq!(clippings[0].clipping.kind, ClippingKind::Highlight);
+    assert_eq!((clippings[0].clipping.location.as_str(), clippings[0].clipping.page.as_str()), ("170-172", "12"));
+    assert!(clippings[0].clipping.added_at.is_some());
+    assert_eq!(clippings[1].clipping.kind, ClippingKind::Note);
+    assert_eq!(clippings[1].clipping.content, "Crowley!\nTwice.");
+
+    assert_eq!((clippings[2].title.as_str(), clippings[2].author.as_str()), ("The Black Swan (Second Edition)", "Nassim Nicholas Taleb"));
+    assert_eq!(clippings[2].clipping.location, "1020-1022");
+    assert!(clippings[2].clipping.added_at.is_some());
+
+    assert!(parseClippings("Mort (Terry Pratchett)\nno meta line\n==========").is_err());
+    assert!(parseClippings("").unwrap().is_empty());
+}
+
+/// A list with only the book the first clippings belong to
+#[cfg(test)]
+fn createGoodOmensBackend() -> readinglist::SqliteBackend {
+    let backend = readinglist::SqliteBackend::new(":memory:").unwrap();
+    backend.addEntry(&ReadingEntry {
+        id: -1,
+        title: String::from("Good Omens"),
+        author: String::from("Neil Gaiman"),
+        genre: String::new(),
+        format: Format::Book,
+        status: Status::Finished,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: std::collections::BTreeMap::new()
+    }).unwrap();
+    return backend;
+}
+
+#[test]
+fn test_failed_clipping_imports_store_nothing() {
+    use readinglist::fields::FieldType;
+
+    let backend = createGoodOmensBackend();
+    // the book not in the list yet can't be added, after Good Omens got its clippings
+    let fields = vec![FieldDefinition { name: String::from("bad\nname"), t: FieldType::Text, default: Some(String::from("x")) }];
+    let clippings = parseClippings(CLIPPINGS).unwrap();
+
+    assert!(importClippings(&backend, &clippings, &fields, false).is_err());
+    assert!(backend.getClippings(1).unwrap().is_empty(), "The clippings stored before are undone");
+    assert!(matches!(backend.getById(2), Err(Error::NotFound(2))), "The new book is undone");
+}
+
+#[test]
+fn test_reimporting_clippings_changes_nothing() {
+    use readinglist::fields::FieldType;
+
+    let backend = createGoodOmensBackend();
+    let fields = vec![FieldDefinition::new("signed", FieldType::Bool, Some("no")).unwrap()];
+    let clippings = parseClippings(CLIPPINGS).unwrap();
+
+    let dryRun = importClippings(&backend, &clippings, &fields, true).unwrap();
+    assert_eq!(dryRun[0], BookImport { title: String::from("Good Omens"), author: String::from("Neil Gaiman, Terry Pratchett"), id: Some(1), created: false, added: 2, known: 0 });
+    assert_eq!((dryRun[1].id, dryRun[1].created, dryRun[1].added), (None, true, 1));
+    assert!(backend.getClippings(1).unwrap().is_empty());
+    assert!(matches!(backend.getById(2), Err(Error::NotFound(2))), "A dry run adds no book");
+
+    let imported = importClippings(&backend, &clippings, &fields, false).unwrap();
+    assert_eq!((imported[1].id, imported[1].created, imported[1].added), (Some(2), true, 1));
+    let swan = backend.getById(2).unwrap();
+    assert_eq!((swan.format, swan.status), (Format::Kindle, Status::Reading));
+    assert_eq!(swan.fields.get("signed"), Some(&String::from("false")), "Defaults of custom fields apply");
+    assert_eq!(backend.getClippings(1).unwrap().len(), 2);
+
+    let again = importClippings(&backend, &clippings, &fields, false).unwrap();
+    assert!(again.iter().all(|b| !b.created && b.added == 0), "{:?}", again);
+    assert_eq!(again.iter().map(|b| b.known).sum::<usize>(), 3);
+    assert!(matches!(backend.getById(3), Err(Error::NotFound(3))), "The new book is found again");
+    assert!(report(&again, false).ends_with("Added 0 items and 0 highlights and notes, 3 were imported before"));
+}
@@ -1,58 +1,530 @@
-use rusqlite::{Connection, Result};
-use rusqlite::{NO_PARAMS, MappedRows, types::FromSql, types::FromSqlResult, types::ValueRef};
+use rusqlite::{Connection, Row, params};
+use rusqlite::{NO_PARAMS, types::FromSql, types::FromSqlError, types::FromSqlResult, types::ValueRef};
+use rusqlite::functions::FunctionFlags;
 
-#[derive(Debug)]
+use chrono::NaiveDateTime;
+
+use std::fmt;
+use std::collections::BTreeMap;
+
+pub mod migrations;
+pub mod fields;
+
+#[derive(Debug, Clone)]
 pub struct ReadingEntry {
     pub id: i64,
     pub author: String,
     pub title: String,
     pub genre: String,
     pub format: Format,
-    pub status: String,
-    pub tags: Vec<String>
+    pub status: Status,
+    pub tags: Vec<String>,
+
+    // Maintained by the backend in UTC. None for entries not stored yet.
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+
+    // Maintained by changeStatus in UTC
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+
+    // Values of the custom fields by name, in the canonical form of
+    // `FieldDefinition::parse`. Unset fields are missing.
+    pub fields: BTreeMap<String, String>
 }
 
-#[derive(Debug)]
+impl ReadingEntry {
+    /// Moves the entry to `next` if its lifecycle allows it. See `enterStatus`
+    /// for the timestamps maintained along the way.
+    pub fn changeStatus(&mut self, next: Status, now: NaiveDateTime) -> std::result::Result<(), InvalidTransition> {
+        if !self.status.canTransitionTo(&next) {
+            return Err(InvalidTransition { from: self.status, to: next });
+        }
+
+        self.enterStatus(next, now);
+        return Ok(());
+    }
+
+    /// Sets the status without checking the lifecycle. Starting to read sets
+    /// `started_at`, unless the entry was only paused, and forgets about an
+    /// earlier finish. Finishing sets `finished_at`.
+    pub fn enterStatus(&mut self, next: Status, now: NaiveDateTime) {
+        if next == self.status {
+            return;
+        }
+
+        match next {
+            Status::Reading if self.status != Status::Paused || self.started_at.is_none() => {
+                self.started_at = Some(now);
+                self.finished_at = None;
+            },
+            Status::Finished => {
+                self.finished_at = Some(now);
+            },
+            _ => {}
+        }
+
+        self.status = next;
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Format {
     Book,
-    Kindle
+    Kindle,
+    Ebook,
+    Audiobook,
+    Article,
+    Paper,
+    Podcast,
+    Video,
+
+    // A format the user came up with, written as other:<name>
+    Other(String)
+}
+
+/// The names of the builtin formats as accepted by `stringToFormatEnum`
+pub const FORMATS: [&str; 8] = ["book", "kindle", "ebook", "audiobook", "article", "paper", "podcast", "video"];
+
+/// Error for a format that is neither builtin nor explicitly user defined
+#[derive(Debug, PartialEq)]
+pub struct UnknownFormat(pub String);
+
+impl fmt::Display for UnknownFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Unknown format '{}'. Use one of {} or other:<name> for a format of your own",
+                      self.0, FORMATS.join(", "));
+    }
 }
 
 impl FromSql for Format {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        match value.as_str() {
-            Ok(s) => {
-                return Ok(Format::Book);
-            },
-            _ => return Ok(Format::Kindle),
+        let s = value.as_str()?;
+
+        // Values stored by older versions may be anything. Keep them as they
+        // are instead of refusing to load the entry.
+        match stringToFormatEnum(s) {
+            Ok(x) => return Ok(x),
+            Err(_) => return Ok(Format::Other(s.trim().to_string()))
         }
     }
 }
 
-pub fn stringToFormatEnum(toConvert: &str) -> Format {
-    let lc = toConvert.to_lowercase();
-    if lc == "book" {
-        return Format::Book;
-    } else if lc == "kindle" {
-        return Format::Kindle
-    } else {
-        return Format::Book
+/// Parses a format as entered by the user. An empty string is a book.
+pub fn stringToFormatEnum(toConvert: &str) -> std::result::Result<Format, UnknownFormat> {
+    let lc = toConvert.trim().to_lowercase();
+    match lc.as_str() {
+        "" | "book" => return Ok(Format::Book),
+        "kindle" => return Ok(Format::Kindle),
+        "ebook" | "e-book" => return Ok(Format::Ebook),
+        "audiobook" => return Ok(Format::Audiobook),
+        "article" => return Ok(Format::Article),
+        "paper" => return Ok(Format::Paper),
+        "podcast" => return Ok(Format::Podcast),
+        "video" => return Ok(Format::Video),
+        _ => {}
+    }
+
+    if lc.starts_with("other:") {
+        let name = toConvert.trim()[6..].trim();
+        if name.is_empty() {
+            return Err(UnknownFormat(toConvert.to_string()));
+        }
+        // other:book is just a book
+        match stringToFormatEnum(name) {
+            Ok(Format::Other(_)) | Err(_) => return Ok(Format::Other(name.to_string())),
+            Ok(x) => return Ok(x)
+        }
     }
+
+    return Err(UnknownFormat(toConvert.to_string()));
 }
 
 pub fn formatEnumToString(f: &Format) -> String {
     match f {
         Format::Book => return String::from("book"),
-        Format::Kindle => return String::from("kindle")
+        Format::Kindle => return String::from("kindle"),
+        Format::Ebook => return String::from("ebook"),
+        Format::Audiobook => return String::from("audiobook"),
+        Format::Article => return String::from("article"),
+        Format::Paper => return String::from("paper"),
+        Format::Podcast => return String::from("podcast"),
+        Format::Video => return String::from("video"),
+        Format::Other(x) => return format!("other:{}", x)
     };
 }
 
+#[test]
+fn test_format_round_trip() {
+    let formats = vec![
+        Format::Book, Format::Kindle, Format::Ebook, Format::Audiobook, Format::Article,
+        Format::Paper, Format::Podcast, Format::Video, Format::Other(String::from("Zine"))
+    ];
+    for f in formats {
+        assert_eq!(stringToFormatEnum(&formatEnumToString(&f)), Ok(f.clone()));
+
+        let conn = Connection::open_in_memory().unwrap();
+        let fromDb: Format = conn.query_row("select ?1", &[formatEnumToString(&f)], |row| row.get(0)).unwrap();
+        assert_eq!(fromDb, f);
+    }
+
+    assert_eq!(stringToFormatEnum(" Kindle "), Ok(Format::Kindle));
+    assert_eq!(stringToFormatEnum("e-book"), Ok(Format::Ebook));
+    assert_eq!(stringToFormatEnum("other:Paper"), Ok(Format::Paper));
+    assert_eq!(stringToFormatEnum("kindel"), Err(UnknownFormat(String::from("kindel"))));
+    assert_eq!(stringToFormatEnum("other:"), Err(UnknownFormat(String::from("other:"))));
+}
+
+/// Where an entry is in its lifecycle. `Status::canTransitionTo` tells which
+/// status may follow another.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Wishlist,
+    Queued,
+    Reading,
+    Paused,
+    Finished,
+    Abandoned
+}
+
+/// The names of all statuses as accepted by `stringToStatusEnum`
+pub const STATUSES: [&str; 6] = ["wishlist", "queued", "reading", "paused", "finished", "abandoned"];
+
+/// Free text statuses used before the lifecycle was introduced and what
+/// they mean today. Compared case insensitively.
+pub const STATUS_ALIASES: [(&str, Status); 19] = [
+    ("", Status::Wishlist),
+    ("unread", Status::Wishlist),
+    ("want to read", Status::Wishlist),
+    ("to read", Status::Wishlist),
+    ("to-read", Status::Wishlist),
+    ("next", Status::Queued),
+    ("up next", Status::Queued),
+    ("started", Status::Reading),
+    ("in progress", Status::Reading),
+    ("currently reading", Status::Reading),
+    ("currently-reading", Status::Reading),
+    ("on hold", Status::Paused),
+    ("read", Status::Finished),
+    ("done", Status::Finished),
+    ("completed", Status::Finished),
+    ("dropped", Status::Abandoned),
+    ("dnf", Status::Abandoned),
+    ("gave up", Status::Abandoned),
+    ("did not finish", Status::Abandoned),
+];
+
+impl Status {
+    pub fn canTransitionTo(&self, next: &Status) -> bool {
+        if self == next {
+            return true;
+        }
+
+        match self {
+            Status::Wishlist => return [Status::Queued, Status::Reading, Status::Abandoned].contains(next),
+            Status::Queued => return [Status::Wishlist, Status::Reading, Status::Abandoned].contains(next),
+            Status::Reading => return [Status::Paused, Status::Finished, Status::Abandoned].contains(next),
+            Status::Paused => return [Status::Reading, Status::Finished, Status::Abandoned].contains(next),
+            // reading it again
+            Status::Finished => return *next == Status::Reading,
+            Status::Abandoned => return [Status::Wishlist, Status::Queued, Status::Reading].contains(next)
+        }
+    }
+}
+
+/// Error for a status that is neither one of `STATUSES` nor a known alias
+#[derive(Debug, PartialEq)]
+pub struct UnknownStatus(pub String);
+
+impl fmt::Display for UnknownStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Unknown status '{}'. Use one of {}", self.0, STATUSES.join(", "));
+    }
+}
+
+impl std::error::Error for UnknownStatus {}
+
+/// Error for a status change the lifecycle does not allow
+#[derive(Debug, PartialEq)]
+pub struct InvalidTransition {
+    pub from: Status,
+    pub to: Status
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let allowed: Vec<String> = STATUSES.iter()
+            .map(|x| stringToStatusEnum(x).unwrap())
+            .filter(|x| *x != self.from && self.from.canTransitionTo(x))
+            .map(|x| statusEnumToString(&x))
+            .collect();
+
+        return write!(f, "Can not change the status from {} to {}. Possible are {}",
+                      statusEnumToString(&self.from), statusEnumToString(&self.to), allowed.join(", "));
+    }
+}
+
+impl FromSql for Status {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match stringToStatusEnum(value.as_str()?) {
+            Ok(x) => return Ok(x),
+            Err(e) => return Err(FromSqlError::Other(Box::new(e)))
+        }
+    }
+}
+
+pub fn stringToStatusEnum(toConvert: &str) -> std::result::Result<Status, UnknownStatus> {
+    let lc = toConvert.trim().to_lowercase();
+    match lc.as_str() {
+        "wishlist" => return Ok(Status::Wishlist),
+        "queued" => return Ok(Status::Queued),
+        "reading" => return Ok(Status::Reading),
+        "paused" => return Ok(Status::Paused),
+        "finished" => return Ok(Status::Finished),
+        "abandoned" => return Ok(Status::Abandoned),
+        _ => {}
+    }
+
+    for (alias, status) in STATUS_ALIASES.iter() {
+        if *alias == lc {
+            return Ok(*status);
+        }
+    }
+
+    return Err(UnknownStatus(toConvert.to_string()));
+}
+
+pub fn statusEnumToString(s: &Status) -> String {
+    match s {
+        Status::Wishlist => return String::from("wishlist"),
+        Status::Queued => return String::from("queued"),
+        Status::Reading => return String::from("reading"),
+        Status::Paused => return String::from("paused"),
+        Status::Finished => return String::from("finished"),
+        Status::Abandoned => return String::from("abandoned")
+    };
+}
+
+#[test]
+fn test_status_lifecycle() {
+    let day = |d: u32| chrono::NaiveDate::from_ymd_opt(2024, 1, d).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    let mut re = ReadingEntry {
+        id: 1,
+        author: String::new(),
+        title: String::new(),
+        genre: String::new(),
+        format: Format::Book,
+        status: Status::Wishlist,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: BTreeMap::new()
+    };
+
+    assert_eq!(re.changeStatus(Status::Finished, day(1)), Err(InvalidTransition { from: Status::Wishlist, to: Status::Finished }));
+    assert_eq!(re.status, Status::Wishlist);
+
+    re.changeStatus(Status::Reading, day(2)).unwrap();
+    assert_eq!(re.started_at, Some(day(2)));
+
+    re.changeStatus(Status::Paused, day(3)).unwrap();
+    re.changeStatus(Status::Reading, day(4)).unwrap();
+    assert_eq!(re.started_at, Some(day(2)), "Resuming keeps the start");
+
+    re.changeStatus(Status::Finished, day(5)).unwrap();
+    assert_eq!(re.finished_at, Some(day(5)));
+    assert!(re.changeStatus(Status::Abandoned, day(6)).is_err());
+
+    re.changeStatus(Status::Reading, day(7)).unwrap();
+    assert_eq!(re.started_at, Some(day(7)), "Reading again starts over");
+    assert_eq!(re.finished_at, None);
+
+    assert_eq!(stringToStatusEnum(" Read "), Ok(Status::Finished));
+    assert_eq!(stringToStatusEnum("DNF"), Ok(Status::Abandoned));
+    assert_eq!(stringToStatusEnum("whenever"), Err(UnknownStatus(String::from("whenever"))));
+}
+
+/// A filter expressed as SQL. `clause` is used as the where clause when
+/// selecting from `reading_entries` and may refer to its columns. Every `?`
+/// in the clause is bound to the corresponding element of `params`.
+///
+/// Besides the builtin functions of SQLite the clause may use `casefold(x)`,
+/// which lowercases `x` the same way `str::to_lowercase` does.
+#[derive(Debug, PartialEq)]
+pub struct SqlFilter {
+    pub clause: String,
+    pub params: Vec<String>
+}
+
+impl SqlFilter {
+    /// A filter that selects every entry
+    pub fn all() -> SqlFilter {
+        return SqlFilter { clause: String::from("1"), params: Vec::new() }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortDirection {
+    Asc,
+    Desc
+}
+
+/// Sorts entries by the named column
+#[derive(Debug, PartialEq, Clone)]
+pub struct SortKey {
+    pub column: String,
+    pub direction: SortDirection
+}
+
+/// Order and window of the entries to retrieve. Entries not covered by
+/// `order` come in the order they were added.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Page {
+    pub order: Vec<SortKey>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>
+}
+
+/// Everything that can go wrong when working with the reading list
+#[derive(Debug)]
+pub enum Error {
+    /// The database could not be read or written
+    Storage(rusqlite::Error),
+    /// There is no entry with the given id
+    NotFound(i64),
+    /// There is no tag with the given name
+    TagNotFound(String),
+    /// A value was rejected, e.g. an unknown status or a forbidden status change
+    Validation(String),
+    /// A query could not be parsed or executed
+    Query(String),
+    /// The terminal or a file could not be read or written
+    Io(std::io::Error)
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Storage(e) => return write!(f, "Could not access the database: {}", e),
+            Error::NotFound(id) => return write!(f, "There is no item with id {}", id),
+            Error::TagNotFound(name) => return write!(f, "There is no tag '{}'", name),
+            Error::Validation(msg) => return write!(f, "{}", msg),
+            Error::Query(msg) => return write!(f, "{}", msg),
+            Error::Io(e) => return write!(f, "Input/output failed: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Storage(e) => return Some(e),
+            Error::Io(e) => return Some(e),
+            _ => return None
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Error {
+        return Error::Storage(e);
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        return Error::Io(e);
+    }
+}
+
+impl From<UnknownFormat> for Error {
+    fn from(e: UnknownFormat) -> Error {
+        return Error::Validation(e.to_string());
+    }
+}
+
+impl From<UnknownStatus> for Error {
+    fn from(e: UnknownStatus) -> Error {
+        return Error::Validation(e.to_string());
+    }
+}
+
+impl From<InvalidTransition> for Error {
+    fn from(e: InvalidTransition) -> Error {
+        return Error::Validation(e.to_string());
+    }
+}
+
+/// A tag and the number of entries tagged with it
+#[derive(Debug, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub entries: i64
+}
+
+/// What a clipping of an entry holds
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ClippingKind {
+    Highlight,
+    Note
+}
+
+pub fn clippingKindToString(k: &ClippingKind) -> String {
+    match k {
+        ClippingKind::Highlight => return String::from("highlight"),
+        ClippingKind::Note => return String::from("note")
+    }
+}
+
+/// A highlight or note made while reading an entry
+#[derive(Debug, PartialEq, Clone)]
+pub struct Clipping {
+    pub entry_id: i64,
+    pub kind: ClippingKind,
+    // where in the entry, e.g. `170-172` on a Kindle. Empty if unknown.
+    pub location: String,
+    pub page: String,
+    // in UTC
+    pub added_at: Option<NaiveDateTime>,
+    pub content: String
+}
+
 pub trait Backend {
     fn getById(&self, id: i64) -> Result<ReadingEntry>;
     fn deleteById(&self, id: i64) -> Result<ReadingEntry>;
-    fn updateEntry(&self, toUpdate: &ReadingEntry);
-    fn addEntry(&self, e: &ReadingEntry);
+    /// Fails with `Error::NotFound` if there is no entry with the id of `toUpdate`
+    fn updateEntry(&self, toUpdate: &ReadingEntry) -> Result<()>;
+    /// Returns the id of the new entry. The id of `e` is ignored, its
+    /// `created_at` kept if given, e.g. by an import.
+    fn addEntry(&self, e: &ReadingEntry) -> Result<i64>;
+    /// Puts back a deleted entry as it was, with its id and timestamps. Fails
+    /// with `Error::Validation` if the id is taken.
+    fn restoreEntry(&self, re: &ReadingEntry) -> Result<()>;
     fn getAllEntries(&self) -> Result<Vec<ReadingEntry>>;
+    fn getEntriesWhere(&self, filter: &SqlFilter, page: &Page) -> Result<Vec<ReadingEntry>>;
+
+    /// All tags in use, ordered by name
+    fn getAllTags(&self) -> Result<Vec<Tag>>;
+    /// Renames a tag on all entries. Fails if a tag named `to` exists already, merge them instead.
+    fn renameTag(&self, from: &str, to: &str) -> Result<()>;
+    /// Replaces the tags `from` with `into` on all entries
+    fn mergeTags(&self, from: &Vec<String>, into: &str) -> Result<()>;
+    /// Removes a tag from all entries
+    fn deleteTag(&self, name: &str) -> Result<()>;
+
+    /// Stores the clipping unless its entry has the same one already, and
+    /// tells whether it did. Fails with `Error::NotFound` if there is no such entry.
+    fn addClipping(&self, clipping: &Clipping) -> Result<bool>;
+    /// The clippings of an entry in the order they appear in it
+    fn getClippings(&self, entryId: i64) -> Result<Vec<Clipping>>;
+
+    /// Runs `f` in a single transaction. The changes it makes are stored all
+    /// together, or none of them if it fails.
+    fn atomically(&self, f: &mut dyn FnMut() -> Result<()>) -> Result<()>;
 }
 
 pub struct SqliteBackend {
@@ -62,98 +534,289 @@ pub struct SqliteBackend {
 impl SqliteBackend {
     pub fn new(db_name: &str) -> Result<SqliteBackend> {
         let conn = Connection::open(db_name)?;
-        conn.execute(
-            " create table if not exists reading_entries (
-                id integer primary key autoincrement,
-                title text not null default '',
-                author text not null default '',
-                genre text  not null default '',
-                format text not null default '',
-                tags text not null default '',
-                status text not null default '',
-                created_at timestamp default current_timestamp,
-                updated_at timestamp default current_timestamp
-                );
-            ", NO_PARAMS,)?;
+        migrations::migrate(&conn, false)?;
+        // can not be changed within the transaction of the migrations
+        conn.execute_batch("pragma foreign_keys = on")?;
+
+        // SQLite's lower() only knows about ASCII. Filters use casefold() to
+        // ignore case exactly like the in memory evaluation of a query does.
+        conn.create_scalar_function(
+            "casefold",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let value = ctx.get::<String>(0)?;
+                return Ok(value.to_lowercase());
+            })?;
 
         return Ok(SqliteBackend {
             conn
         })
     }
+
+    /// Starts a transaction, unless `atomically` started one already
+    fn begin(&self) -> Result<Changes> {
+        if self.conn.is_autocommit() {
+            return Ok(Changes::Own(self.conn.unchecked_transaction()?));
+        }
+        return Ok(Changes::Within(&self.conn));
+    }
 }
 
-impl Backend for SqliteBackend {
-    fn getById(&self, id: i64) -> Result<ReadingEntry> {
-        let mut stmt = self.conn.prepare("SELECT id, title, author, genre, format, tags, status FROM reading_entries where id = ?1")?;
+/// The changes of a single write, in a transaction of their own or in the
+/// one of `atomically`
+enum Changes<'a> {
+    Own(rusqlite::Transaction<'a>),
+    Within(&'a Connection)
+}
 
-        let entry = stmt.query_row(&[id], |row| {
-            let tags_from_db: String = row.get(5)?;
-            let splits = tags_from_db.split(" ");
+impl<'a> std::ops::Deref for Changes<'a> {
+    type Target = Connection;
 
-            // gibt es hierfuer nicht schon was in der std lib?
-            let mut vec: Vec<String> = Vec::new();
-            for s in splits {
-                vec.push(String::from(s));
-            }
+    fn deref(&self) -> &Connection {
+        match self {
+            Changes::Own(tx) => return tx,
+            Changes::Within(conn) => return conn
+        }
+    }
+}
 
-            return Ok(ReadingEntry {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                author: row.get(2)?,
-                genre: row.get(3)?,
-                format: row.get(4)?,
-                status: row.get(6)?,
-                tags: vec
-            })
-        })?;
+impl<'a> Changes<'a> {
+    /// Within `atomically` the changes are committed with all others
+    fn commit(self) -> Result<()> {
+        if let Changes::Own(tx) = self {
+            tx.commit()?;
+        }
+        return Ok(());
+    }
+}
+
+/// Timestamps are stored as text in the format SQLite's current_timestamp uses
+fn timestampToSql(timestamp: &Option<NaiveDateTime>) -> Option<String> {
+    return timestamp.map(|x| x.format("%Y-%m-%d %H:%M:%S").to_string());
+}
+
+const SORTABLE_COLUMNS: [&str; 11] = ["id", "title", "author", "genre", "format", "tags", "status", "created_at", "updated_at", "started_at", "finished_at"];
+
+/// The columns `entryFromRow` expects. Tags are joined by TAG_SEPARATOR,
+/// custom fields are joined by TAG_SEPARATOR as well, with FIELD_SEPARATOR
+/// between name and value.
+const ENTRY_COLUMNS: &str = "id, title, author, genre, format, \
+    (select group_concat(value, char(31)) from (select value from entry_tag_names where entry_id = reading_entries.id order by position)) as tags, \
+    status, created_at, updated_at, started_at, finished_at, \
+    (select group_concat(name || char(30) || value, char(31)) from entry_fields where entry_id = reading_entries.id) as fields";
 
-        Ok(entry)
+const TAG_SEPARATOR: char = '\u{1f}';
+const FIELD_SEPARATOR: char = '\u{1e}';
+
+/// Sorting by a custom field uses `field:<name>` as column, or
+/// `field:<name>:number` to sort its values as numbers
+fn fieldSortExpression(column: &str) -> Option<String> {
+    let parts: Vec<&str> = column.split(":").collect();
+    let name = match parts.as_slice() {
+        ["field", name] | ["field", name, "number"] => name,
+        _ => return None
+    };
+    // the name ends up in the statement and can not be bound
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        return None;
+    }
+
+    let value = format!("(select value from entry_fields where entry_id = reading_entries.id and name = '{}')", name);
+    if parts.len() == 3 {
+        return Some(format!("cast({} as real)", value));
+    }
+    return Some(value);
+}
+
+/// Replaces the custom field values of the entry with the given id
+fn setFields(conn: &Connection, id: i64, fields: &BTreeMap<String, String>) -> Result<()> {
+    conn.execute("delete from entry_fields where entry_id = ?1", &[id])?;
+    for (name, value) in fields {
+        if value.is_empty() {
+            continue;
+        }
+        if name.chars().chain(value.chars()).any(|c| c.is_control()) {
+            return Err(Error::Validation(format!("The field '{}' contains control characters", name.escape_debug())));
+        }
+        conn.execute("insert into entry_fields (entry_id, name, value) values (?1, ?2, ?3)", params![id, name, value])?;
+    }
+
+    return Ok(());
+}
+
+/// Trims the tags and drops empty and duplicate ones
+fn normalizeTags(tags: &Vec<String>) -> Result<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.chars().any(|c| c.is_control()) {
+            return Err(Error::Validation(format!("The tag '{}' contains control characters", tag.escape_debug())));
+        }
+        if tag.contains(',') {
+            return Err(Error::Validation(format!("The tag '{}' contains a comma, which separates tags", tag)));
+        }
+        if !tag.is_empty() && !normalized.iter().any(|x| x == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+
+    return Ok(normalized);
+}
+
+/// Replaces the tags of the entry with the given id
+fn setTags(conn: &Connection, id: i64, tags: &Vec<String>) -> Result<()> {
+    conn.execute("delete from entry_tags where entry_id = ?1", &[id])?;
+    for tag in normalizeTags(tags)? {
+        conn.execute("insert or ignore into tags (name) values (?1)", &[&tag])?;
+        conn.execute("insert into entry_tags (entry_id, tag_id) select ?1, id from tags where name = ?2", params![id, &tag])?;
+    }
+
+    return removeUnusedTags(conn);
+}
+
+fn removeUnusedTags(conn: &Connection) -> Result<()> {
+    conn.execute("delete from tags where id not in (select tag_id from entry_tags)", NO_PARAMS)?;
+    return Ok(());
+}
+
+fn getTagId(conn: &Connection, name: &str) -> Result<i64> {
+    match conn.query_row("select id from tags where name = ?1", &[name], |row| row.get(0)) {
+        Ok(id) => return Ok(id),
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Err(Error::TagNotFound(name.to_string())),
+        Err(e) => return Err(Error::Storage(e))
+    }
+}
+
+/// Looks up a tag by the name a user typed, which is normalized like the
+/// tags of an entry are
+fn findTag(conn: &Connection, name: &str) -> Result<i64> {
+    match normalizeTags(&vec![name.to_string()])?.first() {
+        Some(tag) => return getTagId(conn, tag),
+        None => return Err(Error::TagNotFound(name.to_string()))
+    }
+}
+
+/// Marks all entries tagged with the tag as updated
+fn touchEntriesTaggedWith(conn: &Connection, tagId: i64) -> Result<()> {
+    conn.execute("update reading_entries set updated_at = current_timestamp where id in (select entry_id from entry_tags where tag_id = ?1)", &[tagId])?;
+    return Ok(());
+}
+
+impl Backend for SqliteBackend {
+    fn getById(&self, id: i64) -> Result<ReadingEntry> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM reading_entries where id = ?1", ENTRY_COLUMNS))?;
+
+        match stmt.query_row(&[id], entryFromRow) {
+            Ok(entry) => return Ok(entry),
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Err(Error::NotFound(id)),
+            Err(e) => return Err(Error::Storage(e))
+        }
     }
 
     fn deleteById(&self, id: i64) -> Result<ReadingEntry> {
         let entryToDelete = self.getById(id)?;
 
-        let mut stmt = self.conn.prepare("delete from reading_entries where id = ?1")?;
-        stmt.execute(&[id])?;
+        let tx = self.begin()?;
+        tx.execute("delete from entry_tags where entry_id = ?1", &[id])?;
+        tx.execute("delete from entry_fields where entry_id = ?1", &[id])?;
+        tx.execute("delete from clippings where entry_id = ?1", &[id])?;
+        tx.execute("delete from reading_entries where id = ?1", &[id])?;
+        removeUnusedTags(&tx)?;
+        tx.commit()?;
 
         return Ok(entryToDelete);
     }
     
-    fn updateEntry(&self, toUpdate: &ReadingEntry) {
-        let insertString = "update reading_entries set title = ?1, author = ?2, genre = ?3, format = ?4, tags = ?5, status = ?6 where id = ?7;";
-        self.conn.execute(insertString, 
-                          &[&toUpdate.title, &toUpdate.author, &toUpdate.genre, &formatEnumToString(&toUpdate.format), &toUpdate.tags.join(" "), &toUpdate.status, &toUpdate.id.to_string()]);
+    fn updateEntry(&self, toUpdate: &ReadingEntry) -> Result<()> {
+        let tx = self.begin()?;
+        let insertString = "update reading_entries set title = ?1, author = ?2, genre = ?3, format = ?4, status = ?5, started_at = ?6, finished_at = ?7, updated_at = current_timestamp where id = ?8;";
+        let updated = tx.execute(insertString, 
+                          params![&toUpdate.title, &toUpdate.author, &toUpdate.genre, &formatEnumToString(&toUpdate.format), &statusEnumToString(&toUpdate.status), &timestampToSql(&toUpdate.started_at), &timestampToSql(&toUpdate.finished_at), &toUpdate.id])?;
+
+        if updated == 0 {
+            return Err(Error::NotFound(toUpdate.id));
+        }
+
+        setTags(&tx, toUpdate.id, &toUpdate.tags)?;
+        setFields(&tx, toUpdate.id, &toUpdate.fields)?;
+        tx.commit()?;
+        return Ok(());
     }
 
-    fn addEntry(&self, re: &ReadingEntry) {
-        let insertString = "insert into reading_entries (title, author, genre, format, tags, status) values (?1, ?2, ?3, ?4, ?5, ?6);";
-        self.conn.execute(insertString, &[&re.title, &re.author, &re.genre, &formatEnumToString(&re.format), &re.tags.join(" "), &re.status]);
+    fn addEntry(&self, re: &ReadingEntry) -> Result<i64> {
+        let tx = self.begin()?;
+        let insertString = "insert into reading_entries (title, author, genre, format, status, started_at, finished_at, created_at)
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, coalesce(?8, current_timestamp));";
+        tx.execute(insertString, params![&re.title, &re.author, &re.genre, &formatEnumToString(&re.format), &statusEnumToString(&re.status),
+                                         &timestampToSql(&re.started_at), &timestampToSql(&re.finished_at), &timestampToSql(&re.created_at)])?;
+        let id = tx.last_insert_rowid();
+
+        setTags(&tx, id, &re.tags)?;
+        setFields(&tx, id, &re.fields)?;
+        tx.commit()?;
+        return Ok(id);
+    }
+
+    fn restoreEntry(&self, re: &ReadingEntry) -> Result<()> {
+        match self.getById(re.id) {
+            Ok(_) => return Err(Error::Validation(format!("There is an item with id {} already", re.id))),
+            Err(Error::NotFound(_)) => {},
+            Err(e) => return Err(e)
+        }
+
+        let tx = self.begin()?;
+        let insertString = "insert into reading_entries (id, title, author, genre, format, status, created_at, updated_at, started_at, finished_at)
+            values (?1, ?2, ?3, ?4, ?5, ?6, coalesce(?7, current_timestamp), coalesce(?8, current_timestamp), ?9, ?10);";
+        tx.execute(insertString, params![&re.id, &re.title, &re.author, &re.genre, &formatEnumToString(&re.format), &statusEnumToString(&re.status),
+                                         &timestampToSql(&re.created_at), &timestampToSql(&re.updated_at), &timestampToSql(&re.started_at), &timestampToSql(&re.finished_at)])?;
+
+        setTags(&tx, re.id, &re.tags)?;
+        setFields(&tx, re.id, &re.fields)?;
+        tx.commit()?;
+        return Ok(());
     }
 
     fn getAllEntries(&self) -> Result<Vec<ReadingEntry>> {
-        let mut stmt = self.conn.prepare("SELECT id, title, author, genre, format, tags, status FROM reading_entries")?;
+        let mut stmt = self.conn.prepare(&format!("SELECT {} FROM reading_entries", ENTRY_COLUMNS))?;
 
-        let entries = stmt.query_map(NO_PARAMS, |row| {
+        let entries = stmt.query_map(NO_PARAMS, entryFromRow)?;
 
-            let tags_from_db: String = row.get(5)?;
-            let splits = tags_from_db.split(" ");
+        let mut entryList = Vec::new();
+        for e in entries {
+            entryList.push(e?);
+        }
 
-            // gibt es hierfuer nicht schon was in der std lib?
-            let mut vec: Vec<String> = Vec::new();
-            for s in splits {
-                vec.push(String::from(s));
+        return Ok(entryList);
+    }
+
+    fn getEntriesWhere(&self, filter: &SqlFilter, page: &Page) -> Result<Vec<ReadingEntry>> {
+        let mut order = Vec::new();
+        for key in &page.order {
+            // Column names can not be bound as parameters. Only accept the
+            // ones we know of.
+            let column = match fieldSortExpression(&key.column) {
+                Some(x) => x,
+                None if SORTABLE_COLUMNS.contains(&key.column.as_str()) => key.column.clone(),
+                None => return Err(Error::Query(format!("Can not sort by unknown column '{}'", key.column)))
+            };
+            match key.direction {
+                SortDirection::Asc => order.push(format!("{} asc", column)),
+                SortDirection::Desc => order.push(format!("{} desc", column))
             }
+        }
+        // keeps pages stable for entries with equal sort keys
+        order.push(String::from("id asc"));
 
-            return Ok(ReadingEntry {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                author: row.get(2)?,
-                genre: row.get(3)?,
-                format: row.get(4)?,
-                status: row.get(6)?,
-                tags: vec
-            })
-        })?;
+        let query = format!("SELECT {} FROM reading_entries where {} order by {} limit {} offset {}",
+                            ENTRY_COLUMNS,
+                            filter.clause,
+                            order.join(", "),
+                            page.limit.map(|x| x as i64).unwrap_or(-1),
+                            page.offset.unwrap_or(0));
+        let mut stmt = self.conn.prepare(&query)?;
+
+        let entries = stmt.query_map(&filter.params, entryFromRow)?;
 
         let mut entryList = Vec::new();
         for e in entries {
@@ -162,4 +825,300 @@ impl Backend for SqliteBackend {
 
         return Ok(entryList);
     }
+
+    fn getAllTags(&self) -> Result<Vec<Tag>> {
+        let mut stmt = self.conn.prepare("select name, count(entry_id) from tags join entry_tags on entry_tags.tag_id = tags.id group by tags.id order by name")?;
+
+        let tags = stmt.query_map(NO_PARAMS, |row| Ok(Tag { name: row.get(0)?, entries: row.get(1)? }))?;
+
+        let mut tagList = Vec::new();
+        for t in tags {
+            tagList.push(t?);
+        }
+
+        return Ok(tagList);
+    }
+
+    fn renameTag(&self, from: &str, to: &str) -> Result<()> {
+        let to = normalizeTags(&vec![to.to_string()])?;
+        if to.is_empty() {
+            return Err(Error::Validation(String::from("Tags can not be renamed to nothing, delete them instead")));
+        }
+
+        let tx = self.begin()?;
+        let id = findTag(&tx, from)?;
+        match getTagId(&tx, &to[0]) {
+            Ok(toId) if toId == id => return Ok(()),
+            Ok(_) => return Err(Error::Validation(format!("The tag '{}' exists already. Merge the tags instead.", to[0]))),
+            Err(Error::TagNotFound(_)) => {},
+            Err(e) => return Err(e)
+        }
+
+        touchEntriesTaggedWith(&tx, id)?;
+        tx.execute("update tags set name = ?1 where id = ?2", params![&to[0], id])?;
+        tx.commit()?;
+        return Ok(());
+    }
+
+    fn mergeTags(&self, from: &Vec<String>, into: &str) -> Result<()> {
+        let into = normalizeTags(&vec![into.to_string()])?;
+        if into.is_empty() {
+            return Err(Error::Validation(String::from("Tags can not be merged into nothing, delete them instead")));
+        }
+
+        let tx = self.begin()?;
+        tx.execute("insert or ignore into tags (name) values (?1)", &[&into[0]])?;
+        let intoId = getTagId(&tx, &into[0])?;
+
+        for name in from {
+            let id = findTag(&tx, name)?;
+            if id == intoId {
+                continue;
+            }
+
+            touchEntriesTaggedWith(&tx, id)?;
+            tx.execute("insert or ignore into entry_tags (entry_id, tag_id) select entry_id, ?1 from entry_tags where tag_id = ?2", &[intoId, id])?;
+            tx.execute("delete from entry_tags where tag_id = ?1", &[id])?;
+        }
+
+        removeUnusedTags(&tx)?;
+        tx.commit()?;
+        return Ok(());
+    }
+
+    fn deleteTag(&self, name: &str) -> Result<()> {
+        let tx = self.begin()?;
+        let id = findTag(&tx, name)?;
+
+        touchEntriesTaggedWith(&tx, id)?;
+        tx.execute("delete from entry_tags where tag_id = ?1", &[id])?;
+        removeUnusedTags(&tx)?;
+        tx.commit()?;
+        return Ok(());
+    }
+
+    fn addClipping(&self, clipping: &Clipping) -> Result<bool> {
+        self.getById(clipping.entry_id)?;
+
+        let added = self.conn.execute("insert or ignore into clippings (entry_id, kind, location, page, added_at, content) values (?1, ?2, ?3, ?4, ?5, ?6)",
+                                      params![clipping.entry_id, clippingKindToString(&clipping.kind), &clipping.location, &clipping.page,
+                                              &timestampToSql(&clipping.added_at), &clipping.content])?;
+        return Ok(added > 0);
+    }
+
+    fn getClippings(&self, entryId: i64) -> Result<Vec<Clipping>> {
+        // locations like `170-172` are ordered by where they start
+        let mut stmt = self.conn.prepare("select entry_id, kind, location, page, added_at, content from clippings where entry_id = ?1
+                                          order by cast(location as integer), cast(page as integer), id")?;
+
+        let clippings = stmt.query_map(&[entryId], |row| {
+            let kind: String = row.get(1)?;
+            return Ok(Clipping {
+                entry_id: row.get(0)?,
+                kind: if kind == "note" { ClippingKind::Note } else { ClippingKind::Highlight },
+                location: row.get(2)?,
+                page: row.get(3)?,
+                added_at: row.get(4)?,
+                content: row.get(5)?
+            });
+        })?;
+
+        let mut clippingList = Vec::new();
+        for c in clippings {
+            clippingList.push(c?);
+        }
+
+        return Ok(clippingList);
+    }
+
+    fn atomically(&self, f: &mut dyn FnMut() -> Result<()>) -> Result<()> {
+        let tx = self.begin()?;
+        f()?;
+        return tx.commit();
+    }
+}
+
+#[test]
+fn test_writes_report_their_outcome() {
+    let backend = SqliteBackend::new(":memory:").unwrap();
+    let mut re = ReadingEntry {
+        id: -1,
+        author: String::from("Neil Gaiman"),
+        title: String::from("Coraline"),
+        genre: String::new(),
+        format: Format::Book,
+        status: Status::Wishlist,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: BTreeMap::new()
+    };
+
+    assert_eq!(backend.addEntry(&re).unwrap(), 1);
+    assert_eq!(backend.addEntry(&re).unwrap(), 2);
+
+    re.id = 2;
+    re.title = String::from("Stardust");
+    backend.updateEntry(&re).unwrap();
+    assert_eq!(backend.getById(2).unwrap().title, "Stardust");
+
+    re.fields.insert(String::from("pages"), String::from("300"));
+    re.fields.insert(String::from("note"), String::from("Signed, with a dedication"));
+    backend.updateEntry(&re).unwrap();
+    assert_eq!(backend.getById(2).unwrap().fields, re.fields);
+
+    re.fields.insert(String::from("note"), String::new());
+    backend.updateEntry(&re).unwrap();
+    assert_eq!(backend.getById(2).unwrap().fields.keys().collect::<Vec<&String>>(), vec!["pages"], "Empty values unset the field");
+
+    re.tags = vec![String::from("fantasy")];
+    backend.updateEntry(&re).unwrap();
+    let deleted = backend.deleteById(2).unwrap();
+    backend.restoreEntry(&deleted).unwrap();
+    let restored = backend.getById(2).unwrap();
+    assert_eq!((restored.title, restored.tags, restored.fields), (deleted.title.clone(), deleted.tags.clone(), deleted.fields.clone()));
+    assert_eq!(restored.created_at, deleted.created_at);
+    assert!(matches!(backend.restoreEntry(&deleted), Err(Error::Validation(_))), "Restoring must not overwrite an entry");
+
+    re.id = 42;
+    assert!(matches!(backend.updateEntry(&re), Err(Error::NotFound(42))));
+    assert!(matches!(backend.getById(42), Err(Error::NotFound(42))));
+    assert!(matches!(backend.deleteById(42), Err(Error::NotFound(42))));
+
+    // writes within `atomically` are undone together
+    re.title = String::from("Neverwhere");
+    let failed = backend.atomically(&mut || {
+        backend.addEntry(&re)?;
+        backend.updateEntry(&ReadingEntry { id: 1, ..re.clone() })?;
+        return Err(Error::Validation(String::from("Stop")));
+    });
+    assert!(matches!(failed, Err(Error::Validation(_))));
+    assert_eq!(backend.getAllEntries().unwrap().len(), 2);
+    assert_eq!(backend.getById(1).unwrap().title, "Coraline");
+
+    backend.atomically(&mut || { backend.addEntry(&re)?; return Ok(()); }).unwrap();
+    assert_eq!(backend.getAllEntries().unwrap().len(), 3);
+}
+
+#[test]
+fn test_clippings_are_stored_once() {
+    let backend = SqliteBackend::new(":memory:").unwrap();
+    let id = backend.addEntry(&ReadingEntry {
+        id: -1,
+        author: String::from("Nassim Taleb"),
+        title: String::from("Antifragile"),
+        genre: String::new(),
+        format: Format::Kindle,
+        status: Status::Reading,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: BTreeMap::new()
+    }).unwrap();
+    let clipping = |kind: ClippingKind, location: &str, content: &str| Clipping {
+        entry_id: id,
+        kind: kind,
+        location: location.to_string(),
+        page: String::new(),
+        added_at: None,
+        content: content.to_string()
+    };
+
+    assert!(backend.addClipping(&clipping(ClippingKind::Highlight, "1020-1022", "Wind extinguishes a candle")).unwrap());
+    assert!(backend.addClipping(&clipping(ClippingKind::Note, "980", "see Seneca")).unwrap());
+    assert!(!backend.addClipping(&clipping(ClippingKind::Highlight, "1020-1022", "Wind extinguishes a candle")).unwrap());
+    let locations: Vec<String> = backend.getClippings(id).unwrap().into_iter().map(|c| c.location).collect();
+    assert_eq!(locations, vec!["980", "1020-1022"]);
+
+    assert!(matches!(backend.addClipping(&Clipping { entry_id: 42, ..clipping(ClippingKind::Note, "1", "x") }), Err(Error::NotFound(42))));
+    backend.deleteById(id).unwrap();
+    assert!(backend.getClippings(id).unwrap().is_empty());
+}
+
+#[test]
+fn test_tags_can_be_renamed_merged_and_deleted() {
+    let backend = SqliteBackend::new(":memory:").unwrap();
+    let entry = |tags: Vec<&str>| ReadingEntry {
+        id: -1,
+        author: String::new(),
+        title: String::new(),
+        genre: String::new(),
+        format: Format::Book,
+        status: Status::Wishlist,
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: BTreeMap::new()
+    };
+    let tagsOf = |id: i64| -> Vec<String> { backend.getById(id).unwrap().tags };
+    let tag = |name: &str, entries: i64| Tag { name: name.to_string(), entries: entries };
+
+    backend.addEntry(&entry(vec!["web dev", " rust ", "", "rust"])).unwrap();
+    backend.addEntry(&entry(vec!["rustlang", "async"])).unwrap();
+    backend.addEntry(&entry(vec![])).unwrap();
+    assert_eq!(tagsOf(1), vec!["web dev", "rust"]);
+    assert!(tagsOf(3).is_empty());
+    assert_eq!(backend.getAllTags().unwrap(), vec![tag("async", 1), tag("rust", 1), tag("rustlang", 1), tag("web dev", 1)]);
+
+    backend.mergeTags(&vec![String::from("rustlang")], "rust").unwrap();
+    assert_eq!(tagsOf(2), vec!["async", "rust"]);
+
+    assert!(matches!(backend.renameTag("async", "rust"), Err(Error::Validation(_))));
+    assert!(matches!(backend.renameTag("nope", "yes"), Err(Error::TagNotFound(_))));
+    assert!(matches!(backend.renameTag("async", "sci-fi, fantasy"), Err(Error::Validation(_))));
+    assert!(matches!(backend.mergeTags(&vec![String::from("async")], "a,b"), Err(Error::Validation(_))));
+    assert!(matches!(backend.renameTag(" ", "yes"), Err(Error::TagNotFound(_))));
+    backend.renameTag("web dev", "web development").unwrap();
+    assert_eq!(tagsOf(1), vec!["web development", "rust"]);
+    backend.renameTag(" async ", "asynchronous").unwrap();
+    assert_eq!(tagsOf(2), vec!["asynchronous", "rust"]);
+    backend.renameTag("asynchronous ", "async").unwrap();
+
+    backend.deleteTag(" rust").unwrap();
+    assert_eq!(tagsOf(1), vec!["web development"]);
+    assert_eq!(backend.getAllTags().unwrap(), vec![tag("async", 1), tag("web development", 1)]);
+
+    backend.deleteById(2).unwrap();
+    assert_eq!(backend.getAllTags().unwrap(), vec![tag("web development", 1)]);
+}
+
+/// Maps a row selected as `ENTRY_COLUMNS`
+fn entryFromRow(row: &Row) -> rusqlite::Result<ReadingEntry> {
+    // group_concat gives null for entries without tags
+    let tags_from_db: Option<String> = row.get(5)?;
+    let tags: Vec<String> = match tags_from_db {
+        Some(x) => x.split(TAG_SEPARATOR).map(|t| String::from(t)).collect(),
+        None => Vec::new()
+    };
+
+    let fields_from_db: Option<String> = row.get(11)?;
+    let mut fields = BTreeMap::new();
+    if let Some(x) = fields_from_db {
+        for field in x.split(TAG_SEPARATOR) {
+            let mut parts = field.splitn(2, FIELD_SEPARATOR);
+            let name = parts.next().unwrap_or_default();
+            fields.insert(name.to_string(), parts.next().unwrap_or_default().to_string());
+        }
+    }
+
+    return Ok(ReadingEntry {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        author: row.get(2)?,
+        genre: row.get(3)?,
+        format: row.get(4)?,
+        status: row.get(6)?,
+        tags: tags,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+        started_at: row.get(9)?,
+        finished_at: row.get(10)?,
+        fields: fields
+    })
 }
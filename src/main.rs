@@ -2,22 +2,40 @@
 mod querylanguage;
 mod config;
 mod log;
+mod browser;
+mod edit;
+mod output;
+mod export;
+mod import;
+mod goodreads;
+mod kindle;
+mod bibtex;
 
 use crate::log::debug;
 
 use structopt::StructOpt;
 use dialoguer::{Input, theme::ColorfulTheme};
-use rusqlite::{Connection, Result};
+use rusqlite::Connection;
 
 // Insight: Damit der Compiler Trait-Methoden an einer Struct aufrufen kann, muss das Trait
 // mit importiert werden.
-use readinglist::{Backend, SqliteBackend, ReadingEntry, formatEnumToString, stringToFormatEnum};
+use readinglist::{Backend, SqliteBackend, ReadingEntry, Format, UnknownFormat, FORMATS, Page, SqlFilter, formatEnumToString, stringToFormatEnum};
+use readinglist::{Status, STATUSES, statusEnumToString, stringToStatusEnum};
+use readinglist::{Error, Result, Tag, Clipping, ClippingKind};
+use readinglist::migrations;
+use readinglist::fields::{FieldDefinition, FieldType, FIELD_DATE_FORMAT};
 
-use comfy_table::Table;
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use comfy_table::{Cell, Table};
 use comfy_table::presets::UTF8_FULL;
 
-use crate::querylanguage::{eval, parse_query, Modifier, TableRow};
-use crate::config::Config;
+use crate::querylanguage::{eval, parse_query, to_sql, to_sql_page, Query, QueryError, SqlColumn, TableRow, KEYWORDS};
+use crate::config::{Config, Colors, Overrides, configFileLocation, readConfigFile, parseFieldDefinitions, stringToColor};
+use crate::output::OutputFormat;
+use crate::export::ExportFormat;
 
 #[macro_use]
 extern crate lazy_static;
@@ -36,85 +54,471 @@ struct Cli {
     #[structopt(short="i", long = "id", help="Print an additional column showing the id of item")]
     withId: bool,
 
+    #[structopt(short="d", long = "dates", help="Print additional columns showing when an item was created and last updated")]
+    withDates: bool,
+
     #[structopt(short="x", long = "debug", help="Print debug output on stderr")]
     debug: bool,
 
-    #[structopt(short="q", long = "query", default_value="")]
-    q: String,
+    #[structopt(short="q", long = "query")]
+    q: Option<String>,
+
+    #[structopt(short="I", long = "ignore-case", help="Ignore case when comparing values in a query")]
+    ignoreCase: bool,
 
     #[structopt(
         long = "db-file",
-        default_value="~/rdnglst/readinglist.db",
-        help="Use db file under <db_file_location>")]
-    db_file_location: String
+        help="Use db file under <db_file_location> [default: ~/rdnglst/readinglist.db]")]
+    db_file_location: Option<String>,
+
+    #[structopt(long = "columns", help="The columns to show, separated by commas, e.g. `title,author,id`")]
+    columns: Option<String>,
+
+    #[structopt(long = "sort", help="Sort the items like `order by <sort>` unless the query says otherwise, e.g. `author, title`")]
+    sort: Option<String>,
+
+    #[structopt(long = "date-format", help="How to print dates, e.g. `%d.%m.%Y`")]
+    dateFormat: Option<String>,
+
+    #[structopt(long = "no-color", help="Print the table without colours")]
+    noColor: bool,
+
+    #[structopt(short="o", long = "output", default_value = "table", possible_values = &output::OUTPUT_FORMATS,
+                help="How to print the list. All formats but table, markdown and plain contain every field.")]
+    output: String
 }
 
 #[derive(StructOpt)]
 enum Command {
-    #[structopt(about="Add a new item to the list")]
-    Add,
+    #[structopt(about="Add a new item to the list. Prompts for every field unless any is given.")]
+    Add {
+        #[structopt(long = "title")]
+        title: Option<String>,
+
+        #[structopt(long = "author")]
+        author: Option<String>,
+
+        #[structopt(long = "genre")]
+        genre: Option<String>,
 
-    #[structopt(about="Update an item")]
+        #[structopt(long = "format")]
+        format: Option<String>,
+
+        #[structopt(long = "status")]
+        status: Option<String>,
+
+        #[structopt(long = "tag", number_of_values = 1, help="A tag, repeat it for several")]
+        tags: Vec<String>,
+
+        #[structopt(long = "set", number_of_values = 1, help="Set any field, e.g. `--set pages=300`")]
+        set: Vec<String>
+    },
+
+    #[structopt(about="Update an item. Prompts for every field unless changes are given.")]
     Update {
         #[structopt(long = "id", help="The id of the item to update")]
-        id: i64
+        id: i64,
+
+        #[structopt(long = "set", number_of_values = 1, help="Set a field, e.g. `--set status=finished`")]
+        set: Vec<String>,
+
+        #[structopt(long = "add-tag", number_of_values = 1)]
+        addTags: Vec<String>,
+
+        #[structopt(long = "remove-tag", number_of_values = 1)]
+        removeTags: Vec<String>
+    },
+
+    #[structopt(about="Edit items as a TOML document in $EDITOR")]
+    Edit {
+        #[structopt(help="The id of the item", required_unless = "query", conflicts_with = "query")]
+        id: Option<i64>,
+
+        #[structopt(long = "query", help="Edit all items matching the query in one document")]
+        query: Option<String>
     },
 
     #[structopt(about="Remove an item")]
     Rm {
         #[structopt(long = "id", help="The id of the item which should be deleted")]
         id: i64
+    },
+
+    #[structopt(about="Start reading an item")]
+    Start {
+        #[structopt(help="The id of the item")]
+        id: i64
+    },
+
+    #[structopt(about="Mark an item as finished")]
+    Finish {
+        #[structopt(help="The id of the item")]
+        id: i64
+    },
+
+    #[structopt(about="Stop reading an item for good")]
+    Abandon {
+        #[structopt(help="The id of the item")]
+        id: i64
+    },
+
+    #[structopt(about="Maintain the database file")]
+    Db {
+        #[structopt(subcommand)]
+        cmd: DbCommand
+    },
+
+    #[structopt(about="Browse the list on the whole terminal")]
+    Tui,
+
+    #[structopt(about="Write the list as a standalone document, e.g. for a wiki")]
+    Export {
+        #[structopt(long = "format", possible_values = &export::EXPORT_FORMATS)]
+        format: String,
+
+        #[structopt(short="q", long = "query", help="Export the items matching the query [default: the configured query]")]
+        query: Option<String>,
+
+        #[structopt(long = "group-by", default_value = "status", possible_values = &export::GROUP_BY, help="Put the items of a document into a section per status or genre")]
+        groupBy: String,
+
+        #[structopt(long = "sort", help="Sort the items within a section like `order by <sort>` [default: the configured sort, else title]")]
+        sort: Option<String>,
+
+        #[structopt(long = "template", help="The template of a document [default: the one configured for the format, else a builtin one]")]
+        template: Option<String>,
+
+        #[structopt(long = "title", default_value = "Reading list", help="The title of the document")]
+        title: String
+    },
+
+    #[structopt(about="Print the highlights and notes of an item")]
+    Clippings {
+        #[structopt(help="The id of the item")]
+        id: i64
+    },
+
+    #[structopt(about="Add items from other services")]
+    Import {
+        #[structopt(subcommand)]
+        cmd: ImportCommand
+    },
+
+    #[structopt(about="Inspect the configuration")]
+    Config {
+        #[structopt(subcommand)]
+        cmd: ConfigCommand
+    },
+
+    #[structopt(about="Manage tags across all items")]
+    Tags {
+        #[structopt(subcommand)]
+        cmd: TagsCommand
+    }
+}
+
+#[derive(StructOpt)]
+enum TagsCommand {
+    #[structopt(about="List all tags with the number of items using them, most used first")]
+    List {
+        #[structopt(long = "by-name", help="Sort the tags by name instead")]
+        byName: bool
+    },
+
+    #[structopt(about="Rename a tag on all items")]
+    Rename {
+        old: String,
+        new: String
+    },
+
+    #[structopt(about="Replace tags with another one on all items, e.g. `tags merge a b into c`")]
+    Merge {
+        #[structopt(required = true, min_values = 3)]
+        args: Vec<String>
+    },
+
+    #[structopt(about="Remove a tag from all items")]
+    Rm {
+        tag: String
+    },
+
+    #[structopt(about="Tag an item")]
+    Add {
+        #[structopt(help="The id of the item")]
+        id: i64,
+        #[structopt(required = true)]
+        tags: Vec<String>
+    },
+
+    #[structopt(about="Remove tags from an item")]
+    Remove {
+        #[structopt(help="The id of the item")]
+        id: i64,
+        #[structopt(required = true)]
+        tags: Vec<String>
     }
 }
 
-fn promptForUpdate(toUpdate: &ReadingEntry) -> ReadingEntry {
+#[derive(StructOpt)]
+enum ImportCommand {
+    #[structopt(about="Add the books of a Goodreads export (My Books > Import and export > Export library)")]
+    Goodreads {
+        #[structopt(help="The exported CSV file")]
+        file: String,
+
+        #[structopt(long = "dry-run", help="Only print what would be added")]
+        dryRun: bool
+    },
+
+    #[structopt(about="Add the entries of a BibTeX file, e.g. papers")]
+    Bibtex {
+        #[structopt(help="The .bib file")]
+        file: String,
+
+        #[structopt(long = "dry-run", help="Only print what would be added")]
+        dryRun: bool
+    },
+
+    #[structopt(about="Add the highlights and notes of a Kindle to the items they were made in")]
+    KindleClippings {
+        #[structopt(help="The `My Clippings.txt` file in the documents folder of the Kindle")]
+        file: String,
+
+        #[structopt(long = "dry-run", help="Only print what would be added")]
+        dryRun: bool
+    }
+}
+
+#[derive(StructOpt)]
+enum ConfigCommand {
+    #[structopt(about="Print the effective configuration and where each setting came from")]
+    Show
+}
+
+#[derive(StructOpt)]
+enum DbCommand {
+    #[structopt(about="Upgrade the database to the schema of this version. Happens on every start anyway.")]
+    Migrate {
+        #[structopt(long = "dry-run", help="Only check whether the migrations would succeed, don't change the database")]
+        dryRun: bool
+    }
+}
+
+fn promptForUpdate(toUpdate: &ReadingEntry, fields: &Vec<FieldDefinition>) -> ReadingEntry {
     let title: String = create_promt_for(&String::from("Title"), Some(&toUpdate.title));
     let author: String = create_promt_for(&String::from("Author"), Some(&toUpdate.author));
-    let status: String = create_promt_for(&String::from("Status"), Some(&toUpdate.status));
-    let format: String = create_promt_for(&String::from("Format"), Some(&formatEnumToString(&toUpdate.format)));
+    let status: Status = create_status_prompt(Some(&toUpdate.status));
+    let format: Format = create_format_prompt(Some(&toUpdate.format));
     let genre: String = create_promt_for(&String::from("Genre"), Some(&toUpdate.genre));
-    let tags: String = create_promt_for(&String::from("Tags (space separated)"), Some(&toUpdate.tags.join(" ")));
+    let tags: String = create_promt_for(&String::from("Tags (comma separated)"), Some(&toUpdate.tags.join(", ")));
 
-    let splittedTags = tags.split(" ").map(|x| String::from(x)).collect();
+    let splittedTags = splitTags(&tags);
 
-    let re = ReadingEntry{
+    // values of fields no longer declared are kept
+    let mut fieldValues = toUpdate.fields.clone();
+    for def in fields {
+        fieldValues.insert(def.name.clone(), create_field_prompt(def, toUpdate.fields.get(&def.name)));
+    }
+
+    let mut re = ReadingEntry{
         id: toUpdate.id, // Not needed here
         author: author,
         title: title,
-        format: stringToFormatEnum(&format),
+        format: format,
         genre: genre,
-        status: status,
-        tags: splittedTags
+        status: toUpdate.status,
+        tags: splittedTags,
+        created_at: toUpdate.created_at,
+        updated_at: toUpdate.updated_at,
+        started_at: toUpdate.started_at,
+        finished_at: toUpdate.finished_at,
+        fields: fieldValues
     };
+    // the prompt only accepts allowed transitions
+    re.changeStatus(status, now()).unwrap();
 
     return re;
 }
 
-fn add() -> ReadingEntry {
+fn add(fields: &Vec<FieldDefinition>) -> ReadingEntry {
     let title: String = create_promt_for(&String::from("Title"), None);
     let author: String = create_promt_for(&String::from("Author"), None);
-    let status: String = create_promt_for(&String::from("Status"), None);
-    let format: String = create_promt_for(&String::from("Format"), None);
+    let status: Status = create_status_prompt(None);
+    let format: Format = create_format_prompt(None);
     let genre: String = create_promt_for(&String::from("Genre"), None);
-    let tags: String = create_promt_for(&String::from("Tags (space separated)"), None);
+    let tags: String = create_promt_for(&String::from("Tags (comma separated)"), None);
 
-    let splits = tags.split(" ");
+    let splittedTags = splitTags(&tags);
 
-    let splittedTags = tags.split(" ").map(|x| String::from(x)).collect();
+    let mut fieldValues = BTreeMap::new();
+    for def in fields {
+        fieldValues.insert(def.name.clone(), create_field_prompt(def, def.default.as_ref()));
+    }
 
-    let re = ReadingEntry{
+    let mut re = ReadingEntry{
         id: -1000, // Not needed here
         author: author,
         title: title,
-        format: stringToFormatEnum(&format),
+        format: format,
         genre: genre,
-        status: status,
-        tags: splittedTags
+        status: Status::Wishlist,
+        tags: splittedTags,
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: fieldValues
     };
+    re.enterStatus(status, now());
 
     return re;
 }
 
+/// Tags may contain spaces, so they are entered separated by commas. The
+/// backend takes care of surrounding whitespace and empty tags.
+fn splitTags(tags: &str) -> Vec<String> {
+    return tags.split(",").map(|x| String::from(x)).collect();
+}
+
+/// Timestamps and the id are maintained by readinglist itself
+fn isEditable(column: &str, fields: &Vec<FieldDefinition>) -> bool {
+    match column {
+        "title" | "author" | "genre" | "format" | "status" | "tags" => return true,
+        _ => return fields.iter().any(|def| def.name == column)
+    }
+}
+
+/// Sets the column of an entry to a value typed by the user. Status changes
+/// have to be allowed by the lifecycle.
+fn setColumn(re: &mut ReadingEntry, column: &str, value: &str, fields: &Vec<FieldDefinition>) -> Result<()> {
+    match column {
+        "title" => re.title = value.trim().to_string(),
+        "author" => re.author = value.trim().to_string(),
+        "genre" => re.genre = value.trim().to_string(),
+        "format" => re.format = stringToFormatEnum(value)?,
+        "status" => re.changeStatus(stringToStatusEnum(value)?, now())?,
+        "tags" => re.tags = splitTags(value),
+        _ => {
+            match fields.iter().find(|def| def.name == column) {
+                Some(def) => { re.fields.insert(def.name.clone(), def.parse(value)?); },
+                None if createColumnVec(fields).iter().any(|x| x == column) => {
+                    return Err(Error::Validation(format!("{} can not be edited", columnTitle(column))));
+                },
+                None => return Err(Error::Validation(format!("Unknown field '{}'", column)))
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Splits `--set` arguments like `pages=300` into the field and its value
+fn parseAssignments(args: &Vec<String>) -> Result<Vec<(String, String)>> {
+    let mut assignments = Vec::new();
+    for arg in args {
+        match arg.find('=') {
+            Some(i) => assignments.push((arg[..i].trim().to_lowercase(), arg[i + 1..].to_string())),
+            None => return Err(Error::Validation(format!("'{}' doesn't set a field. Use <field>=<value>", arg)))
+        }
+    }
+
+    return Ok(assignments);
+}
+
+/// Prompts only make sense if someone can answer them
+fn isInteractive() -> bool {
+    return termion::is_tty(&std::io::stdin());
+}
+
+/// Creates an entry from the values given on the command line. Fields not
+/// given get their default. The title is the only field required, it is
+/// asked for if `interactive`.
+fn addFromArgs(assignments: &Vec<(String, String)>, tags: &Vec<String>, fields: &Vec<FieldDefinition>, interactive: bool) -> Result<ReadingEntry> {
+    let mut re = ReadingEntry {
+        id: -1000, // Not needed here
+        author: String::new(),
+        title: String::new(),
+        format: Format::Book,
+        genre: String::new(),
+        status: Status::Wishlist,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: fields.iter().filter_map(|def| def.default.clone().map(|x| (def.name.clone(), x))).collect()
+    };
+
+    for (column, value) in assignments {
+        // a new entry may start anywhere in the lifecycle
+        if column == "status" {
+            re.enterStatus(stringToStatusEnum(value)?, now());
+        } else {
+            setColumn(&mut re, column, value, fields)?;
+        }
+    }
+    re.tags.extend(tags.iter().cloned());
+
+    if re.title.is_empty() {
+        if !interactive {
+            return Err(Error::Validation(String::from("An item needs a title. Use --title")));
+        }
+        re.title = create_promt_for(&String::from("Title"), None).trim().to_string();
+    }
+
+    return Ok(re);
+}
+
+/// Applies the changes given on the command line. Status changes have to be
+/// allowed by the lifecycle.
+fn updateFromArgs(re: &mut ReadingEntry, assignments: &Vec<(String, String)>, addTags: &Vec<String>, removeTags: &Vec<String>, fields: &Vec<FieldDefinition>) -> Result<()> {
+    for (column, value) in assignments {
+        setColumn(re, column, value, fields)?;
+    }
+
+    re.tags.extend(addTags.iter().cloned());
+    for tag in removeTags {
+        let tag = tag.trim();
+        if !re.tags.iter().any(|t| t == tag) {
+            return Err(Error::Validation(format!("Item {} is not tagged with '{}'", re.id, tag)));
+        }
+        re.tags.retain(|t| t != tag);
+    }
+
+    return Ok(());
+}
+
+#[test]
+fn test_entries_can_be_added_and_updated_from_flags() {
+    let fields = vec![
+        FieldDefinition::new("pages", FieldType::Number, None).unwrap(),
+        FieldDefinition::new("source", FieldType::Text, Some("library")).unwrap()
+    ];
+    let set = |args: Vec<&str>| parseAssignments(&args.iter().map(|x| x.to_string()).collect()).unwrap();
+
+    let mut re = addFromArgs(&set(vec!["title=Dune", "author=Frank Herbert", "status=finished", "pages=612.0"]),
+                             &vec![String::from("sf"), String::from("classic")], &fields, false).unwrap();
+    assert_eq!((re.title.as_str(), re.author.as_str(), re.status), ("Dune", "Frank Herbert", Status::Finished));
+    assert!(re.finished_at.is_some());
+    assert_eq!(re.tags, vec!["sf", "classic"]);
+    assert_eq!(re.fields.get("pages"), Some(&String::from("612")));
+    assert_eq!(re.fields.get("source"), Some(&String::from("library")), "Defaults apply to fields not given");
+
+    assert!(matches!(addFromArgs(&set(vec!["author=Frank Herbert"]), &Vec::new(), &fields, false), Err(Error::Validation(_))),
+            "Without a terminal a missing title can't be asked for");
+    assert!(addFromArgs(&set(vec!["title=Dune", "isbn=123"]), &Vec::new(), &fields, false).is_err());
+    assert!(addFromArgs(&set(vec!["title=Dune", "created=2024-01-01"]), &Vec::new(), &fields, false).is_err());
+    assert!(parseAssignments(&vec![String::from("title")]).is_err());
+
+    updateFromArgs(&mut re, &set(vec!["status=reading", "source=friend"]), &vec![String::from("reread")], &vec![String::from("sf")], &fields).unwrap();
+    assert_eq!(re.status, Status::Reading);
+    assert_eq!(re.tags, vec!["classic", "reread"]);
+    assert_eq!(re.fields.get("source"), Some(&String::from("friend")));
+
+    assert!(matches!(updateFromArgs(&mut re, &set(vec!["status=wishlist"]), &Vec::new(), &Vec::new(), &fields), Err(Error::Validation(_))),
+            "Status changes follow the lifecycle");
+    assert!(updateFromArgs(&mut re, &Vec::new(), &Vec::new(), &vec![String::from("sf")], &fields).is_err());
+}
+
 fn create_promt_for(item: &str, initial_text: Option<&String>) -> String {
     return Input::with_theme(&ColorfulTheme::default())
         .with_prompt(item)
@@ -123,33 +527,133 @@ fn create_promt_for(item: &str, initial_text: Option<&String>) -> String {
         .unwrap();
 }
 
-fn print_table(entries: &Vec<ReadingEntry>, withId: bool) -> Table {
-    let mut headers = vec!["Title", "Author", "Genre", "Status", "Format", "Tags"];
-    if withId {
-        headers.push("ID")
+/// Prompts for a format until the user enters one `stringToFormatEnum` accepts
+fn create_format_prompt(initial: Option<&Format>) -> Format {
+    let format: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(&format!("Format ({}, other:<name>)", FORMATS.join(", ")))
+        .with_initial_text(initial.map(|f| formatEnumToString(f)).unwrap_or_default())
+        .validate_with(|input: &String| -> std::result::Result<(), UnknownFormat> {
+            return stringToFormatEnum(input).map(|_| ());
+        })
+        .interact_text()
+        .unwrap();
+
+    return stringToFormatEnum(&format).unwrap();
+}
+
+/// Prompts for a status until the user enters one `stringToStatusEnum` accepts
+/// and, when updating, `current` may change to
+fn create_status_prompt(current: Option<&Status>) -> Status {
+    let status: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(&format!("Status ({})", STATUSES.join(", ")))
+        .with_initial_text(current.map(|s| statusEnumToString(s)).unwrap_or_default())
+        .validate_with(|input: &String| -> std::result::Result<(), String> {
+            let next = stringToStatusEnum(input).map_err(|e| e.to_string())?;
+            match current {
+                Some(x) if !x.canTransitionTo(&next) => {
+                    return Err(readinglist::InvalidTransition { from: *x, to: next }.to_string());
+                },
+                _ => return Ok(())
+            }
+        })
+        .interact_text()
+        .unwrap();
+
+    return stringToStatusEnum(&status).unwrap();
+}
+
+/// Prompts for the value of a custom field until the user enters one the
+/// field accepts. Returns the value in its canonical form.
+fn create_field_prompt(def: &FieldDefinition, initial: Option<&String>) -> String {
+    let hint = match &def.t {
+        FieldType::Text => String::new(),
+        FieldType::Number => String::from(" (number)"),
+        FieldType::Date => String::from(" (YYYY-MM-DD)"),
+        FieldType::Bool => String::from(" (yes/no)"),
+        FieldType::Enum(values) => format!(" ({})", values.join(", "))
+    };
+
+    let value: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(&format!("{}{}", def.name, hint))
+        .with_initial_text(initial.cloned().unwrap_or_default())
+        .allow_empty(true)
+        .validate_with(|input: &String| -> std::result::Result<(), String> {
+            return def.parse(input).map(|_| ()).map_err(|e| e.to_string());
+        })
+        .interact_text()
+        .unwrap();
+
+    return def.parse(&value).unwrap();
+}
+
+fn now() -> NaiveDateTime {
+    return chrono::Utc::now().naive_utc();
+}
+
+/// Moves the entry with the given id to `next` if the lifecycle allows it
+fn changeStatusOf(backend: &dyn Backend, id: i64, next: Status) -> Result<()> {
+    let mut re = backend.getById(id)?;
+    re.changeStatus(next, now())?;
+
+    return backend.updateEntry(&re);
+}
+
+fn formatTimestamp(timestamp: &Option<NaiveDateTime>, dateFormat: &str) -> String {
+    match timestamp {
+        Some(x) => return x.format(dateFormat).to_string(),
+        None => return String::new()
+    }
+}
+
+/// Where and when the clipping was made, e.g. `Highlight at location 170-172, page 12, 2024-03-03 10:15`
+fn describeClipping(c: &Clipping, dateFormat: &str) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if !c.location.is_empty() {
+        parts.push(format!("location {}", c.location));
+    }
+    if !c.page.is_empty() {
+        parts.push(format!("page {}", c.page));
+    }
+    if c.added_at.is_some() {
+        parts.push(formatTimestamp(&c.added_at, dateFormat));
     }
 
+    let kind = match c.kind {
+        ClippingKind::Highlight => "Highlight",
+        ClippingKind::Note => "Note"
+    };
+    if parts.is_empty() {
+        return kind.to_string();
+    }
+    return format!("{} at {}", kind, parts.join(", "));
+}
+
+/// Prints the given columns of the entries, see `Config::columns`. Rows are
+/// coloured by the status of their entry.
+/// Colours the cells only if `terminal` tells that stdout is one, so pipes
+/// and files get plain text
+fn print_table(entries: &Vec<ReadingEntry>, columns: &Vec<String>, dateFormat: &str, colors: &Colors, terminal: bool) -> Table {
+    let colored = |cell: Cell, color: Option<&String>| -> Cell {
+        match color.and_then(|x| stringToColor(x)) {
+            Some(x) if colors.enabled && terminal => return cell.fg(x),
+            _ => return cell
+        }
+    };
+
+    let headers: Vec<Cell> = columns.iter()
+        .map(|col| colored(Cell::new(columnTitle(col)), colors.header.as_ref()))
+        .collect();
+
     let mut table = Table::new();
     table
         .set_header(headers)
         .load_preset(UTF8_FULL);
 
     for e in entries {
-        let tags = e.tags.join(" ");
-        let formatString = formatEnumToString(&e.format);
-        let mut row = vec![
-          &e.title,
-          &e.author,
-          &e.genre,
-          &e.status,
-          &formatString,
-          &tags
-        ];
-
-        let idString = &e.id.to_string();
-        if withId {
-            row.push(idString);
-        }
+        let color = colors.statuses.get(&statusEnumToString(&e.status));
+        let row: Vec<Cell> = columns.iter()
+            .map(|col| colored(Cell::new(columnValue(e, col, dateFormat)), color))
+            .collect();
 
         table.add_row(row);
     }
@@ -157,34 +661,442 @@ fn print_table(entries: &Vec<ReadingEntry>, withId: bool) -> Table {
     return table
 }
 
-fn createHeaderVec() -> Vec<String> {
-    return vec![
+#[test]
+fn test_tables_are_only_coloured_on_a_terminal() {
+    let backend = createFixtureBackend("colors");
+    let entries = backend.getAllEntries().unwrap();
+    let columns = vec![String::from("title"), String::from("status")];
+    let mut statuses = BTreeMap::new();
+    statuses.insert(String::from("reading"), String::from("green"));
+    let colors = Colors { enabled: true, header: Some(String::from("cyan")), statuses: statuses };
+
+    // comfy_table styles only on a terminal as well, which tests don't run on
+    let render = |terminal: bool| print_table(&entries, &columns, "%Y-%m-%d", &colors, terminal).enforce_styling().to_string();
+    assert!(render(true).contains("\u{1b}["));
+    assert!(!render(false).contains("\u{1b}["), "Pipes and files get no escape codes");
+}
+
+fn columnTitle(col: &str) -> String {
+    match col {
+        "id" => return String::from("ID"),
+        "title" | "author" | "genre" | "status" | "format" | "tags" | "created" | "updated" | "started" | "finished" => {
+            return col[..1].to_uppercase() + &col[1..];
+        },
+        // custom fields keep the name they were declared with
+        _ => return col.to_string()
+    }
+}
+
+fn columnValue(re: &ReadingEntry, col: &str, dateFormat: &str) -> String {
+    match col {
+        "id" => return re.id.to_string(),
+        "title" => return re.title.clone(),
+        "author" => return re.author.clone(),
+        "genre" => return re.genre.clone(),
+        "status" => return statusEnumToString(&re.status),
+        "format" => return formatEnumToString(&re.format),
+        "tags" => return re.tags.join(", "),
+        "created" => return formatTimestamp(&re.created_at, dateFormat),
+        "updated" => return formatTimestamp(&re.updated_at, dateFormat),
+        "started" => return formatTimestamp(&re.started_at, dateFormat),
+        "finished" => return formatTimestamp(&re.finished_at, dateFormat),
+        _ => return re.fields.get(col).cloned().unwrap_or_default()
+    }
+}
+
+fn createHeaderVec(fields: &Vec<FieldDefinition>) -> Vec<String> {
+    let mut headers = vec![
         "author".to_string(),
+        "created".to_string(),
+        "finished".to_string(),
         "format".to_string(),
         "genre".to_string(),
+        "started".to_string(),
         "status".to_string(),
-        "title".to_string()
+        "tags".to_string(),
+        "title".to_string(),
+        "updated".to_string()
+    ];
+
+    let mut custom: Vec<String> = fields.iter().map(|def| def.name.clone()).collect();
+    custom.sort();
+    headers.extend(custom);
+
+    return headers;
+}
+
+/// Maps the columns of `createHeaderVec` to their storage in the database
+fn createSqlColumns(fields: &Vec<FieldDefinition>) -> HashMap<String, SqlColumn> {
+    let mut columns = HashMap::new();
+    for col in &["author", "format", "genre", "status", "title"] {
+        columns.insert(col.to_string(), SqlColumn::Text(col.to_string()));
+    }
+    columns.insert("tags".to_string(), SqlColumn::Linked { column: "tags".to_string(), view: "entry_tag_names".to_string() });
+    columns.insert("created".to_string(), SqlColumn::Timestamp("created_at".to_string()));
+    columns.insert("updated".to_string(), SqlColumn::Timestamp("updated_at".to_string()));
+    columns.insert("started".to_string(), SqlColumn::Timestamp("started_at".to_string()));
+    columns.insert("finished".to_string(), SqlColumn::Timestamp("finished_at".to_string()));
+    for def in fields {
+        columns.insert(def.name.clone(), SqlColumn::Field { name: def.name.clone(), t: def.t.clone() });
+    }
+
+    return columns;
+}
+
+/// Returns all entries matching the query. Lets the backend do the filtering
+/// if the query can be expressed in SQL and evaluates it in memory otherwise.
+fn filterEntries(backend: &dyn Backend, query: &Query, fields: &Vec<FieldDefinition>) -> Result<Vec<ReadingEntry>> {
+    let sqlColumns = createSqlColumns(fields);
+    let page = to_sql_page(&query.page, &sqlColumns);
+
+    match to_sql(query, &sqlColumns) {
+        Some(filter) => {
+            debug(&format!("Filtering in the database with {:?}", filter));
+            return backend.getEntriesWhere(&filter, &page);
+        },
+        None => {
+            debug(&"Query can not be expressed in SQL. Filtering in memory".to_string());
+
+            // Paging has to wait until the entries are filtered
+            let sorted = Page { order: page.order.clone(), ..Page::default() };
+            let entries = backend.getEntriesWhere(&SqlFilter::all(), &sorted)?;
+
+            return Ok(entries.into_iter()
+                .filter(|re| eval(query, &mut createTableRowFromReadingEntry(&re, fields)))
+                .skip(page.offset.unwrap_or(0) as usize)
+                .take(page.limit.map(|x| x as usize).unwrap_or(usize::MAX))
+                .collect());
+        }
+    }
+}
+
+/// Creates a database with a handful of entries. Entry n was created on
+/// 2024-01-0n and updated 10 * n days ago, except entry 5 which has never
+/// been updated. Entries being read or finished were started on 2024-02-0n,
+/// finished ones were finished on 2024-03-0n. Some entries have values for
+/// the fields of `createFixtureFields`.
+#[cfg(test)]
+fn createFixtureBackend(name: &str) -> SqliteBackend {
+    // shared, so a second connection can reach it. Lives as long as the backend.
+    let path = format!("file:rdnglst-{}?mode=memory&cache=shared", name);
+
+    let backend = SqliteBackend::new(&path).unwrap();
+    let fixtures = vec![
+        ("American Gods", "Neil Gaiman", "fantasy", "read", "book", "myth, usa"),
+        ("Good Omens", "Neil Gaiman", "fantasy", "reading", "kindle", "humor, , myth"),
+        ("Mort", "Terry Pratchett", "Fantasy", "unread", "book", ""),
+        ("Antifragile", "Nassim Taleb", "essay", "read", "kindle", "stats,ml"),
+        ("Ärger im Paradies", "Ödön Ümlaut", "", "", "book", "Übersetzung"),
+        ("The Sandman", "Neil Gaiman", "comic", "read", "other:Graphic Novel", "myth, graphic novel"),
+    ];
+    for (title, author, genre, status, format, tags) in fixtures {
+        backend.addEntry(&ReadingEntry {
+            id: -1,
+            title: title.to_string(),
+            author: author.to_string(),
+            genre: genre.to_string(),
+            status: stringToStatusEnum(status).unwrap(),
+            format: stringToFormatEnum(format).unwrap(),
+            tags: splitTags(tags),
+            created_at: None,
+            updated_at: None,
+            started_at: None,
+            finished_at: None,
+            fields: BTreeMap::new()
+        }).unwrap();
+    }
+
+    // The backend maintains the timestamps itself, so they are set behind its back
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch("
+        update reading_entries set
+            created_at = '2024-01-0' || id || ' 12:00:00',
+            updated_at = datetime('now', '-' || (id * 10) || ' days');
+        update reading_entries set updated_at = null where id = 5;
+        update reading_entries set started_at = '2024-02-0' || id || ' 08:00:00' where status in ('reading', 'finished');
+        update reading_entries set finished_at = '2024-03-0' || id || ' 20:00:00' where status = 'finished';
+        insert into entry_fields (entry_id, name, value) values
+            (1, 'pages', '635'), (2, 'pages', '412'), (3, 'pages', '96'), (6, 'pages', '2000'),
+            (1, 'source', 'library'), (2, 'source', 'friend'), (4, 'source', 'library'),
+            (1, 'bought', '2023-05-01'), (4, 'bought', '2024-02-10'),
+            (2, 'signed', 'false'), (6, 'signed', 'true'),
+            (3, 'note', 'Rincewind!');
+    ").unwrap();
+
+    return backend;
+}
+
+#[cfg(test)]
+fn createFixtureFields() -> Vec<FieldDefinition> {
+    return vec![
+        FieldDefinition::new("pages", FieldType::Number, None).unwrap(),
+        FieldDefinition::new("source", FieldType::Enum(vec![String::from("library"), String::from("friend")]), None).unwrap(),
+        FieldDefinition::new("bought", FieldType::Date, None).unwrap(),
+        FieldDefinition::new("signed", FieldType::Bool, Some("no")).unwrap(),
+        FieldDefinition::new("note", FieldType::Text, None).unwrap(),
     ];
 }
 
-fn createTableRowFromReadingEntry(re: &ReadingEntry) -> TableRow {
+#[test]
+fn test_sql_and_in_memory_filtering_agree() {
+    let backend = createFixtureBackend("agree");
+    let fields = createFixtureFields();
+
+    let queries = vec![
+        ("author is \"Neil Gaiman\"", false),
+        ("genre is fantasy", true),
+        ("genre is empty or status is wishlist", false),
+        ("title contains o and not genre is essay", false),
+        ("not format is kindle", false),
+        ("format is \"other:Graphic Novel\" or format is paper", false),
+        ("title startswith a", true),
+        ("title endswith s", false),
+        ("title endswith \"\"", false),
+        ("author contains ÖDÖN", true),
+        ("(author startswith Neil or author endswith Taleb) and status is finished", false),
+        ("tags has myth", false),
+        ("tags has MYTH", true),
+        ("tags has all (myth, usa)", false),
+        ("tags has any (ml, humor)", false),
+        ("tags is empty", false),
+        ("not tags is empty", false),
+        ("tags has übersetzung", true),
+        ("tags has \"graphic novel\"", false),
+        ("tags has any (\"Graphic Novel\", usa)", true),
+        ("status has any (finished, reading)", false),
+        ("started after 2024-02-02", false),
+        ("finished is empty and not started is empty", false),
+        ("created after 2024-01-02", false),
+        ("created before 2024-01-03 or created after \"2024-01-04 12:00\"", false),
+        ("created between 2024-01-02 and 2024-01-04", false),
+        ("updated within 25d", false),
+        ("not updated within 25d", false),
+        ("updated is empty", false),
+        ("pages is 412", false),
+        ("pages is empty", false),
+        ("source is LIBRARY", true),
+        ("source has any (library, friend) and not signed is true", false),
+        ("bought after 2024-01-01", false),
+        ("bought is empty", false),
+        ("note contains wind", false),
+    ];
+
+    let columns = createHeaderVec(&fields);
+    let all = backend.getAllEntries().unwrap();
+    for (q, ignore_case) in queries {
+        let query = parse_query(q, &columns, ignore_case).unwrap();
+
+        let mut in_memory: Vec<i64> = all.iter()
+            .filter(|re| eval(&query, &mut createTableRowFromReadingEntry(re, &fields)))
+            .map(|re| re.id)
+            .collect();
+
+        let filter = match to_sql(&query, &createSqlColumns(&fields)) {
+            Some(x) => x,
+            None => panic!("{} should be expressible in SQL", q)
+        };
+        let mut in_sql: Vec<i64> = backend.getEntriesWhere(&filter, &Page::default()).unwrap().iter().map(|re| re.id).collect();
+
+        in_memory.sort();
+        in_sql.sort();
+        assert_eq!(in_memory, in_sql, "Results for {} differ", q);
+    }
+}
+
+#[test]
+fn test_suffixes_that_change_length_when_folded_are_found() {
+    let backend = SqliteBackend::new(":memory:").unwrap();
+    backend.addEntry(&ReadingEntry {
+        id: -1,
+        title: String::from("Gazİ"),
+        author: String::new(),
+        genre: String::new(),
+        format: Format::Book,
+        status: readinglist::Status::Wishlist,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: std::collections::BTreeMap::new()
+    }).unwrap();
+    let fields = Vec::new();
+    let columns = createHeaderVec(&fields);
+
+    // 'İ' folds to two characters, 'i' and a combining dot
+    for q in vec!["title endswith İ", "title endswith zİ", "title endswith \"i\u{307}\""] {
+        let query = parse_query(q, &columns, true).unwrap();
+        let filter = to_sql(&query, &createSqlColumns(&fields)).unwrap();
+        let row = &mut createTableRowFromReadingEntry(&backend.getAllEntries().unwrap()[0], &fields);
+        assert!(eval(&query, row), "{} should match in memory", q);
+        assert_eq!(backend.getEntriesWhere(&filter, &Page::default()).unwrap().len(), 1, "{} should match in SQL", q);
+    }
+}
+
+#[test]
+fn test_paging_is_applied_after_filtering() {
+    let backend = createFixtureBackend("paging");
+    let fields = createFixtureFields();
+    let columns = createHeaderVec(&fields);
+
+    let ids = |q: &str| -> Vec<i64> {
+        let query = parse_query(q, &columns, false).unwrap();
+        return filterEntries(&backend, &query, &fields).unwrap().iter().map(|re| re.id).collect();
+    };
+
+    assert_eq!(ids("order by author desc, title desc"), vec![5, 3, 6, 2, 1, 4]);
+    assert_eq!(ids("order by genre limit 2 offset 1"), vec![3, 6]);
+
+    // matches can not be expressed in SQL, so paging happens in memory
+    assert_eq!(ids("title matches /o/ order by author desc, title limit 2 offset 1"), vec![1, 2]);
+    assert_eq!(ids("title contains o order by author desc, title limit 2 offset 1"), vec![1, 2]);
+    assert_eq!(ids("order by created desc limit 2"), vec![6, 5]);
+
+    // numbers are sorted by their value, entries without one come first
+    assert_eq!(ids("order by pages desc limit 3"), vec![6, 1, 2]);
+    assert_eq!(ids("order by pages limit 3"), vec![4, 5, 3]);
+    assert_eq!(ids("not source is empty order by source"), vec![2, 1, 4]);
+}
+
+fn createTableRowFromReadingEntry(re: &ReadingEntry, fields: &Vec<FieldDefinition>) -> TableRow {
     let mut row = TableRow::new();
     row.insert(&"author".to_string(), &re.author);
     row.insert(&"format".to_string(), &formatEnumToString(&re.format));
     row.insert(&"genre".to_string(), &re.genre);
     row.insert(&"title".to_string(), &re.title);
-    row.insert(&"status".to_string(), &re.status);
+    row.insert(&"status".to_string(), &statusEnumToString(&re.status));
+
+    let tags: Vec<String> = re.tags.iter().filter(|t| !t.is_empty()).cloned().collect();
+    row.insert_list(&"tags".to_string(), &tags);
+
+    let timestamps = vec![
+        ("created", &re.created_at),
+        ("updated", &re.updated_at),
+        ("started", &re.started_at),
+        ("finished", &re.finished_at)
+    ];
+    for (col, timestamp) in timestamps {
+        match timestamp {
+            Some(x) => row.insert_date(&col.to_string(), x),
+            None => row.insert(&col.to_string(), &String::new())
+        }
+    }
+
+    for def in fields {
+        let value = re.fields.get(&def.name).cloned().unwrap_or_default();
+        match (&def.t, NaiveDate::parse_from_str(&value, FIELD_DATE_FORMAT)) {
+            (FieldType::Date, Ok(x)) => row.insert_date(&def.name, &x.and_hms_opt(0, 0, 0).unwrap()),
+            (FieldType::Date, Err(_)) => row.insert(&def.name, &String::new()),
+            _ => row.insert(&def.name, &value)
+        }
+    }
 
     return row;
 }
 
+/// Renders a query error with a caret marker under the offending token
+fn render_query_error(q: &str, err: &QueryError) -> String {
+    let column = q[..err.offset].chars().count();
+    let width = std::cmp::max(err.token.chars().count(), 1);
+
+    return format!("Invalid query: {}\n  {}\n  {}{}",
+                   err,
+                   q,
+                   " ".repeat(column),
+                   "^".repeat(width));
+}
+
+#[test]
+fn test_render_query_error() {
+    let err = QueryError {
+        token: "iss".to_string(),
+        offset: 7,
+        expected: "a comparison operator ('is')".to_string()
+    };
+
+    let rendered = render_query_error("author iss Gaiman", &err);
+    assert_eq!(rendered, "Invalid query: expected a comparison operator ('is') but found 'iss'\n  author iss Gaiman\n         ^^^");
+}
+
+/// The columns `print_table` can show
+fn createColumnVec(fields: &Vec<FieldDefinition>) -> Vec<String> {
+    let mut columns = createHeaderVec(fields);
+    columns.push(String::from("id"));
+
+    return columns;
+}
+
+/// Names custom fields can't have, as builtin columns, their names in SQL or
+/// the query language use them
+fn reservedFieldNames() -> Vec<String> {
+    let mut reserved = createHeaderVec(&Vec::new());
+    reserved.push(String::from("id"));
+    reserved.extend(vec!["created_at", "updated_at", "started_at", "finished_at"].iter().map(|x| x.to_string()));
+    reserved.extend(KEYWORDS.iter().map(|x| x.to_string()));
+    return reserved;
+}
+
+/// Merges the defaults, the config file and the command line, in that order
+fn loadConfig(args: Cli) -> Result<Config> {
+    let reserved = reservedFieldNames();
+
+    let (location, locationSource) = configFileLocation();
+    let path = expandTilde(&location);
+    let doc = readConfigFile(&path)?;
+    let fields = parseFieldDefinitions(&doc, &reserved)?;
+    let knownColumns = createColumnVec(&fields);
+
+    let mut extraColumns: Vec<String> = Vec::new();
+    if args.withDates {
+        extraColumns.extend(vec!["created", "updated", "started", "finished"].iter().map(|x| x.to_string()));
+    }
+    if args.withId {
+        extraColumns.push(String::from("id"));
+    }
+
+    let overrides = Overrides {
+        db_file_location: args.db_file_location,
+        query: args.q,
+        ignore_case: args.ignoreCase,
+        columns: args.columns.map(|x| x.split(",").map(|c| c.to_string()).collect()),
+        extraColumns: extraColumns,
+        sort: args.sort,
+        date_format: args.dateFormat,
+        noColor: args.noColor
+    };
+
+    let mut config = Config::new(fields);
+    config.config_file = path.clone();
+    config.sources.insert(String::from("config_file"), locationSource);
+    config.applyConfigFile(&doc, &path, &knownColumns)?;
+    config.applyOverrides(overrides, &knownColumns)?;
+    config.debug = args.debug;
+    config.db_file_location = expandTilde(&config.db_file_location);
+
+    return Ok(config);
+}
+
+/// Exits if the configuration is invalid
 fn initConfig() -> Config {
-    let args = Cli::from_args();
-    return Config {
-        debug: args.debug,
-        db_file_location: expandTilde(&args.db_file_location),
-        withId: args.withId,
-        query: args.q
+    match loadConfig(Cli::from_args()) {
+        Ok(x) => return x,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(exitCode(&e));
+        }
+    }
+}
+
+/// The order of the query or, if it has none, the configured one
+fn sortOrder(query: &Query, sort: &String, columns: &Vec<String>, ignoreCase: bool) -> Result<Page> {
+    if !query.page.order.is_empty() || sort.trim().is_empty() {
+        return Ok(query.page.clone());
+    }
+
+    let clause = format!("order by {}", sort);
+    match parse_query(&clause, columns, ignoreCase) {
+        Ok(x) => return Ok(Page { order: x.page.order, ..query.page.clone() }),
+        Err(e) => return Err(Error::Query(render_query_error(&clause, &e)))
     }
 }
 
@@ -192,7 +1104,7 @@ fn initConfig() -> Config {
 fn test_expand_tilde() {
     assert_eq!(expandTilde(&"/foo/bar".to_string()), "/foo/bar".to_string());
     assert_eq!(expandTilde(&"~".to_string()), std::env::var("HOME").unwrap());
-    assert_eq!(expandTilde(&"~/foo".to_string()), "/Users/tlongo/foo".to_string());
+    assert_eq!(expandTilde(&"~/foo".to_string()), format!("{}/foo", std::env::var("HOME").unwrap()));
     assert_eq!(expandTilde(&"foo/bar".to_string()), "foo/bar".to_string());
 }
 
@@ -214,6 +1126,32 @@ fn expandTilde(dir: &String) -> String {
     return dir.clone();
 }
 
+/// Applies pending migrations without opening the backend, which would apply them right away
+fn migrateDb(db_file_location: &str, dryRun: bool) -> Result<()> {
+    let conn = Connection::open(db_file_location)?;
+    let from = migrations::schemaVersion(&conn)?;
+    let applied = migrations::migrate(&conn, dryRun)?;
+
+    if applied.is_empty() {
+        println!("Database is up to date (schema version {})", from);
+        return Ok(());
+    }
+
+    for (m, notes) in &applied {
+        println!("{:>4}  {}", m.version, m.description);
+        for note in notes {
+            println!("      {}", note);
+        }
+    }
+    if dryRun {
+        println!("Would migrate from schema version {} to {}. Nothing has been changed.", from, migrations::latestVersion());
+    } else {
+        println!("Migrated from schema version {} to {}", from, migrations::latestVersion());
+    }
+
+    return Ok(());
+}
+
 fn createAppFolderIfNeccessary() -> std::io::Result<()>{
     let expanded = expandTilde(&"~/rdnglst".to_string());
     let app_folder = std::path::Path::new(&expanded);
@@ -225,40 +1163,271 @@ fn createAppFolderIfNeccessary() -> std::io::Result<()>{
     return Ok(())
 }
 
-fn main() -> Result<()>{
+fn runTagsCommand(backend: &dyn Backend, cmd: TagsCommand) -> Result<()> {
+    match cmd {
+        TagsCommand::List{byName} => {
+            let mut tags = backend.getAllTags()?;
+            if !byName {
+                // stable, so equally used tags stay sorted by name
+                tags.sort_by(|a, b| b.entries.cmp(&a.entries));
+            }
+            println!("{}", print_tags_table(&tags));
+        },
+        TagsCommand::Rename{old, new} => backend.renameTag(&old, &new)?,
+        TagsCommand::Merge{args} => {
+            let (from, into) = parseMergeArgs(&args)?;
+            backend.mergeTags(&from, &into)?;
+        },
+        TagsCommand::Rm{tag} => backend.deleteTag(&tag)?,
+        TagsCommand::Add{id, tags} => addTags(backend, id, &tags)?,
+        TagsCommand::Remove{id, tags} => removeTags(backend, id, &tags)?
+    }
+
+    return Ok(());
+}
+
+/// Splits `a b into c` into the tags to merge and the one to merge them into
+fn parseMergeArgs(args: &Vec<String>) -> Result<(Vec<String>, String)> {
+    let usage = || Error::Validation(String::from("Expected the tags to merge followed by 'into' and the tag to merge them into, e.g. `tags merge a b into c`"));
+
+    if args.len() < 3 || args[args.len() - 2] != "into" {
+        return Err(usage());
+    }
+
+    let from = args[..args.len() - 2].to_vec();
+    let into = args[args.len() - 1].clone();
+    return Ok((from, into));
+}
+
+#[test]
+fn test_parse_merge_args() {
+    let args = |x: &str| -> Vec<String> { x.split(" ").map(|s| String::from(s)).collect() };
+
+    assert_eq!(parseMergeArgs(&args("a b into c")).unwrap(), (vec![String::from("a"), String::from("b")], String::from("c")));
+    assert_eq!(parseMergeArgs(&args("into into c")).unwrap(), (vec![String::from("into")], String::from("c")));
+    assert!(parseMergeArgs(&args("a b c")).is_err());
+    assert!(parseMergeArgs(&args("a into")).is_err());
+}
+
+fn addTags(backend: &dyn Backend, id: i64, tags: &Vec<String>) -> Result<()> {
+    let mut re = backend.getById(id)?;
+    re.tags.extend(tags.iter().cloned());
+
+    return backend.updateEntry(&re);
+}
+
+/// Fails if the item is not tagged with one of the tags
+fn removeTags(backend: &dyn Backend, id: i64, tags: &Vec<String>) -> Result<()> {
+    let mut re = backend.getById(id)?;
+    for tag in tags {
+        let tag = tag.trim();
+        if !re.tags.iter().any(|t| t == tag) {
+            return Err(Error::Validation(format!("Item {} is not tagged with '{}'", id, tag)));
+        }
+        re.tags.retain(|t| t != tag);
+    }
+
+    return backend.updateEntry(&re);
+}
+
+#[test]
+fn test_tags_of_a_single_item_can_be_changed() {
+    let backend = createFixtureBackend("tags");
+
+    addTags(&backend, 3, &vec![String::from("disc world"), String::from("humor")]).unwrap();
+    assert_eq!(backend.getById(3).unwrap().tags, vec!["disc world", "humor"]);
+
+    removeTags(&backend, 1, &vec![String::from("usa")]).unwrap();
+    assert_eq!(backend.getById(1).unwrap().tags, vec!["myth"]);
+
+    assert!(matches!(removeTags(&backend, 1, &vec![String::from("usa")]), Err(Error::Validation(_))));
+    assert!(matches!(addTags(&backend, 42, &vec![String::from("usa")]), Err(Error::NotFound(42))));
+}
+
+fn print_tags_table(tags: &Vec<Tag>) -> Table {
+    let mut table = Table::new();
+    table
+        .set_header(vec!["Tag", "Items"])
+        .load_preset(UTF8_FULL);
+
+    for t in tags {
+        table.add_row(vec![t.name.clone(), t.entries.to_string()]);
+    }
+
+    return table;
+}
+
+/// The exit code for each kind of error, so scripts can tell them apart
+fn exitCode(e: &Error) -> i32 {
+    match e {
+        Error::Storage(_) => return 1,
+        Error::Query(_) => return 2,
+        Error::NotFound(_) | Error::TagNotFound(_) => return 3,
+        Error::Validation(_) => return 4,
+        Error::Io(_) => return 5
+    }
+}
+
+#[test]
+fn test_errors_have_distinct_exit_codes() {
+    let errors = vec![
+        Error::Storage(rusqlite::Error::QueryReturnedNoRows),
+        Error::Query(String::new()),
+        Error::NotFound(1),
+        Error::TagNotFound(String::new()),
+        Error::Validation(String::new()),
+        Error::Io(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    ];
+
+    let codes: Vec<i32> = errors.iter().map(exitCode).collect();
+    assert_eq!(codes, vec![1, 2, 3, 3, 4, 5]);
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(exitCode(&e));
+    }
+}
+
+fn run() -> Result<()> {
     let args = Cli::from_args();
 
     createAppFolderIfNeccessary();
 
+    if let Some(Command::Db{cmd: DbCommand::Migrate{dryRun}}) = args.cmd {
+        return migrateDb(&CONFIG.db_file_location, dryRun);
+    }
+    if let Some(Command::Config{cmd: ConfigCommand::Show}) = args.cmd {
+        println!("{}", CONFIG.show());
+        return Ok(());
+    }
+
     let backend = SqliteBackend::new(&CONFIG.db_file_location)?;
 
     match args.cmd {
-        Some(Command::Add) => {
-            let re = add();
-            backend.addEntry(&re)
+        Some(Command::Add{title, author, genre, format, status, tags, set}) => {
+            let mut assignments: Vec<(String, String)> = vec![("title", title), ("author", author), ("genre", genre), ("format", format), ("status", status)]
+                .into_iter()
+                .filter_map(|(column, value)| value.map(|x| (column.to_string(), x)))
+                .collect();
+            assignments.extend(parseAssignments(&set)?);
+
+            let re = if assignments.is_empty() && tags.is_empty() && isInteractive() {
+                add(&CONFIG.fields)
+            } else {
+                addFromArgs(&assignments, &tags, &CONFIG.fields, isInteractive())?
+            };
+            let id = backend.addEntry(&re)?;
+            println!("Added item {}", id);
         },
-        Some(Command::Update{id}) => {
-            let toUpdate = backend.getById(id)?;
-            let updated = promptForUpdate(&toUpdate);
-            backend.updateEntry(&updated);
+        Some(Command::Update{id, set, addTags, removeTags}) => {
+            let mut toUpdate = backend.getById(id)?;
+            if set.is_empty() && addTags.is_empty() && removeTags.is_empty() {
+                if !isInteractive() {
+                    return Err(Error::Validation(String::from("Nothing to update. Use --set, --add-tag or --remove-tag")));
+                }
+                toUpdate = promptForUpdate(&toUpdate, &CONFIG.fields);
+            } else {
+                updateFromArgs(&mut toUpdate, &parseAssignments(&set)?, &addTags, &removeTags, &CONFIG.fields)?;
+            }
+            backend.updateEntry(&toUpdate)?;
+        },
+        Some(Command::Edit{id, query}) => {
+            let entries = match (id, query) {
+                (Some(id), _) => vec![backend.getById(id)?],
+                (None, Some(q)) => {
+                    let columns = createHeaderVec(&CONFIG.fields);
+                    let query = match parse_query(&q, &columns, CONFIG.ignore_case) {
+                        Ok(x) => x,
+                        Err(e) => return Err(Error::Query(render_query_error(&q, &e)))
+                    };
+                    filterEntries(&backend, &query, &CONFIG.fields)?
+                },
+                (None, None) => Vec::new()
+            };
+            if entries.is_empty() {
+                println!("No item matches the query");
+                return Ok(());
+            }
+
+            let changed = edit::editEntries(&backend, &entries, &CONFIG.fields, &CONFIG.date_format, &mut edit::openInEditor)?;
+            println!("Updated {} of {} items", changed, entries.len());
         },
         Some(Command::Rm{id}) => {
-            backend.deleteById(id);
+            backend.deleteById(id)?;
         },
-        None => {
-            let entries = backend.getAllEntries()?;
-            let columns = createHeaderVec();
-            if CONFIG.query != "" {
-                let modifier: Modifier = parse_query(&CONFIG.query, &columns);
-                let filteredEntries = entries.into_iter()
-                    .filter(|re| eval(&modifier, &mut createTableRowFromReadingEntry(&re)))
-                    .collect();
-
-                println!("{}", print_table(&filteredEntries, CONFIG.withId));
-            } else {
-                println!("{}", print_table(&entries, CONFIG.withId));
+        Some(Command::Start{id}) => changeStatusOf(&backend, id, Status::Reading)?,
+        Some(Command::Finish{id}) => changeStatusOf(&backend, id, Status::Finished)?,
+        Some(Command::Abandon{id}) => changeStatusOf(&backend, id, Status::Abandoned)?,
+        Some(Command::Clippings{id}) => {
+            let re = backend.getById(id)?;
+            let clippings = backend.getClippings(id)?;
+            if clippings.is_empty() {
+                println!("'{}' has no highlights or notes", re.title);
             }
+            for c in &clippings {
+                println!("{}\n{}\n", describeClipping(c, &CONFIG.date_format), c.content);
+            }
+        },
+        Some(Command::Import{cmd: ImportCommand::Goodreads{file, dryRun}}) => {
+            let entries = goodreads::fromGoodreadsCsv(&std::fs::read_to_string(&file)?, &CONFIG.fields)?;
+            let results = import::importEntries(&backend, entries, dryRun)?;
+            println!("{}", import::report(&results, dryRun));
+        },
+        Some(Command::Import{cmd: ImportCommand::Bibtex{file, dryRun}}) => {
+            let entries = bibtex::fromBibtex(&std::fs::read_to_string(&file)?, &CONFIG.fields)?;
+            let results = import::importEntries(&backend, entries, dryRun)?;
+            println!("{}", import::report(&results, dryRun));
+        },
+        Some(Command::Import{cmd: ImportCommand::KindleClippings{file, dryRun}}) => {
+            let clippings = kindle::parseClippings(&std::fs::read_to_string(&file)?)?;
+            let results = kindle::importClippings(&backend, &clippings, &CONFIG.fields, dryRun)?;
+            println!("{}", kindle::report(&results, dryRun));
+        },
+        Some(Command::Db{..}) | Some(Command::Config{..}) => {},
+        Some(Command::Tags{cmd}) => runTagsCommand(&backend, cmd)?,
+        Some(Command::Tui) => browser::run(&backend, &CONFIG)?,
+        Some(Command::Export{format, query, groupBy, sort, template, title}) => {
+            let exportFormat = export::stringToExportFormat(&format)?;
+            let groupBy = export::stringToGroupBy(&groupBy)?;
+            let q = query.unwrap_or_else(|| CONFIG.query.clone());
 
+            let columns = createHeaderVec(&CONFIG.fields);
+            let mut parsed: Query = match parse_query(&q, &columns, CONFIG.ignore_case) {
+                Ok(x) => x,
+                Err(e) => return Err(Error::Query(render_query_error(&q, &e)))
+            };
+            let sort = sort.unwrap_or_else(|| CONFIG.sort.clone());
+            let sort = if sort.trim().is_empty() { String::from("title") } else { sort };
+            parsed.page = sortOrder(&parsed, &sort, &columns, CONFIG.ignore_case)?;
+            let entries = filterEntries(&backend, &parsed, &CONFIG.fields)?;
+
+            let document = match exportFormat {
+                ExportFormat::Document(documentFormat) => {
+                    let template = export::loadTemplate(documentFormat, template.as_ref().or_else(|| CONFIG.templates.get(&format)))?;
+                    let date = chrono::Local::now().format(&CONFIG.date_format).to_string();
+                    let options = export::ExportOptions { format: documentFormat, group_by: groupBy, title: title, date: date, template: template };
+                    export::exportEntries(&entries, &options, &CONFIG.fields, &CONFIG.date_format)?
+                },
+                ExportFormat::Bibtex => bibtex::toBibtex(&entries),
+                ExportFormat::CslJson => bibtex::toCslJson(&entries)
+            };
+            print!("{}", document);
+        },
+        None => {
+            let columns = createHeaderVec(&CONFIG.fields);
+            let mut query: Query = match parse_query(&CONFIG.query, &columns, CONFIG.ignore_case) {
+                Ok(x) => x,
+                Err(e) => return Err(Error::Query(render_query_error(&CONFIG.query, &e)))
+            };
+            query.page = sortOrder(&query, &CONFIG.sort, &columns, CONFIG.ignore_case)?;
+            let filteredEntries = filterEntries(&backend, &query, &CONFIG.fields)?;
+
+            match output::stringToOutputFormat(&args.output)? {
+                OutputFormat::Table => println!("{}", print_table(&filteredEntries, &CONFIG.columns, &CONFIG.date_format, &CONFIG.colors, termion::is_tty(&std::io::stdout()))),
+                format => print!("{}", output::render(&filteredEntries, format, &CONFIG.fields, &CONFIG.columns, &CONFIG.date_format))
+            }
         }
     }
 
@@ -0,0 +1,361 @@
+use rusqlite::{Connection, Result, NO_PARAMS, params};
+use rusqlite::ffi;
+
+use crate::{STATUSES, STATUS_ALIASES, statusEnumToString};
+
+/// A step bringing the schema from `version - 1` to `version`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Connection) -> Result<()>,
+    // runs before `apply` to put aside what it would lose, returns what the user should know about it
+    keep: Option<fn(&Connection) -> Result<Vec<String>>>
+}
+
+/// All migrations ordered by version. Never change a step once it has been
+/// released, append a new one instead.
+pub const MIGRATIONS: [Migration; 6] = [
+    Migration {
+        version: 1,
+        description: "Create the reading_entries table",
+        apply: createReadingEntries,
+        keep: None
+    },
+    Migration {
+        version: 2,
+        description: "Add started_at and finished_at and map free text statuses onto the lifecycle",
+        apply: migrateStatus,
+        keep: Some(keepUnknownStatuses)
+    },
+    Migration {
+        version: 3,
+        description: "Move tags into the tables tags and entry_tags",
+        apply: normalizeTags,
+        keep: None
+    },
+    Migration {
+        version: 4,
+        description: "Add the table entry_fields holding the values of custom fields",
+        apply: createEntryFields,
+        keep: None
+    },
+    Migration {
+        version: 5,
+        description: "Add the table clippings holding highlights and notes of entries",
+        apply: createClippings,
+        keep: None
+    },
+    Migration {
+        version: 6,
+        description: "Keep statuses the lifecycle doesn't know in the field legacy_status",
+        apply: moveLegacyStatuses,
+        keep: None
+    },
+];
+
+/// The version a database has after all migrations have been applied
+pub fn latestVersion() -> u32 {
+    return MIGRATIONS.last().map(|m| m.version).unwrap_or(0);
+}
+
+/// The version recorded in the database. Databases created before versioning
+/// was introduced report 0.
+pub fn schemaVersion(conn: &Connection) -> Result<u32> {
+    return conn.query_row("pragma user_version", NO_PARAMS, |row| row.get(0));
+}
+
+/// The migrations not applied to the database yet, in the order they will be applied
+pub fn pendingMigrations(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    let version = schemaVersion(conn)?;
+    if version > latestVersion() {
+        return Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_CANTOPEN),
+            Some(format!("The database has schema version {} but this version of readinglist only knows up to {}. Please upgrade readinglist.",
+                         version, latestVersion()))));
+    }
+
+    return Ok(MIGRATIONS.iter().filter(|m| m.version > version).collect());
+}
+
+/// Applies all pending migrations in a single transaction and returns them
+/// with what the user should know about them. Either all of them are applied
+/// or, if one fails, none. A dry run rolls the transaction back after applying
+/// them, so it fails and reports just like the real run would without
+/// touching the database.
+pub fn migrate(conn: &Connection, dryRun: bool) -> Result<Vec<(&'static Migration, Vec<String>)>> {
+    let pending = pendingMigrations(conn)?;
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    // what steps put aside for later ones, only for the run
+    tx.execute_batch("create temp table legacy_statuses (entry_id integer primary key, status text not null)")?;
+
+    let mut applied = Vec::new();
+    for m in pending {
+        let notes = match m.keep {
+            Some(keep) => keep(&tx)?,
+            None => Vec::new()
+        };
+        (m.apply)(&tx)?;
+        // pragmas don't take parameters
+        tx.execute_batch(&format!("pragma user_version = {}", m.version))?;
+        applied.push((m, notes));
+    }
+
+    if dryRun {
+        tx.rollback()?;
+    } else {
+        tx.execute_batch("drop table temp.legacy_statuses")?;
+        tx.commit()?;
+    }
+
+    return Ok(applied);
+}
+
+fn createReadingEntries(conn: &Connection) -> Result<()> {
+    // Databases created before versioning have the table already
+    conn.execute(
+        " create table if not exists reading_entries (
+            id integer primary key autoincrement,
+            title text not null default '',
+            author text not null default '',
+            genre text  not null default '',
+            format text not null default '',
+            tags text not null default '',
+            status text not null default '',
+            created_at timestamp default current_timestamp,
+            updated_at timestamp default current_timestamp
+            );
+        ", NO_PARAMS,)?;
+
+    return Ok(());
+}
+
+/// Adds the lifecycle timestamps and maps free text statuses onto `Status`.
+/// Anything unknown ends up on the wishlist.
+fn migrateStatus(conn: &Connection) -> Result<()> {
+    // Databases written by versions without migrations may have the columns already
+    let mut stmt = conn.prepare("select name from pragma_table_info('reading_entries')")?;
+    let columns = stmt.query_map(NO_PARAMS, |row| row.get::<usize, String>(0))?
+        .collect::<Result<Vec<String>>>()?;
+
+    for col in &["started_at", "finished_at"] {
+        if !columns.iter().any(|x| x == col) {
+            conn.execute(&format!("alter table reading_entries add column {} timestamp", col), NO_PARAMS)?;
+        }
+    }
+
+    for (alias, status) in STATUS_ALIASES.iter() {
+        conn.execute("update reading_entries set status = ?1 where lower(trim(status)) = ?2",
+                     &[statusEnumToString(status), alias.to_string()])?;
+    }
+    conn.execute("update reading_entries set status = lower(trim(status)) where lower(trim(status)) in ('wishlist', 'queued', 'reading', 'paused', 'finished', 'abandoned')", NO_PARAMS)?;
+    conn.execute("update reading_entries set status = 'wishlist' where status not in ('wishlist', 'queued', 'reading', 'paused', 'finished', 'abandoned')", NO_PARAMS)?;
+
+    return Ok(());
+}
+
+/// Puts aside the statuses `migrateStatus` doesn't know, as it puts them on
+/// the wishlist. `moveLegacyStatuses` stores them in a field.
+fn keepUnknownStatuses(conn: &Connection) -> Result<Vec<String>> {
+    let rows = {
+        let mut stmt = conn.prepare("select id, title, status from reading_entries order by id")?;
+        let rows = stmt.query_map(NO_PARAMS, |row| Ok((row.get::<usize, i64>(0)?, row.get::<usize, String>(1)?, row.get::<usize, String>(2)?)))?;
+        rows.collect::<Result<Vec<(i64, String, String)>>>()?
+    };
+
+    let mut notes = Vec::new();
+    for (id, title, status) in rows {
+        // like lower(trim(status)) in `migrateStatus`
+        let known = status.trim_matches(' ').to_ascii_lowercase();
+        if STATUSES.contains(&known.as_str()) || STATUS_ALIASES.iter().any(|(alias, _)| *alias == known) {
+            continue;
+        }
+
+        conn.execute("insert into temp.legacy_statuses (entry_id, status) values (?1, ?2)", params![id, &status])?;
+        notes.push(format!("Item {} '{}': the unknown status '{}' became wishlist, it is kept in the field legacy_status", id, title, status));
+    }
+
+    return Ok(notes);
+}
+
+/// Moves the space separated tags column into the tables `tags` and
+/// `entry_tags`, so tag names may contain spaces. The view `entry_tag_names`
+/// lists the tags of every entry in the order they were added.
+fn normalizeTags(conn: &Connection) -> Result<()> {
+    let oldTags = {
+        let mut stmt = conn.prepare("select id, tags from reading_entries")?;
+        let rows = stmt.query_map(NO_PARAMS, |row| Ok((row.get::<usize, i64>(0)?, row.get::<usize, String>(1)?)))?;
+        rows.collect::<Result<Vec<(i64, String)>>>()?
+    };
+
+    // SQLite can only drop columns by copying the table
+    conn.execute_batch("
+        create table reading_entries_new (
+            id integer primary key autoincrement,
+            title text not null default '',
+            author text not null default '',
+            genre text  not null default '',
+            format text not null default '',
+            status text not null default '',
+            created_at timestamp default current_timestamp,
+            updated_at timestamp default current_timestamp,
+            started_at timestamp,
+            finished_at timestamp
+        );
+        insert into reading_entries_new (id, title, author, genre, format, status, created_at, updated_at, started_at, finished_at)
+            select id, title, author, genre, format, status, created_at, updated_at, started_at, finished_at from reading_entries;
+        drop table reading_entries;
+        alter table reading_entries_new rename to reading_entries;
+
+        create table tags (
+            id integer primary key autoincrement,
+            name text not null unique
+        );
+        create table entry_tags (
+            entry_id integer not null references reading_entries(id) on delete cascade,
+            tag_id integer not null references tags(id) on delete cascade,
+            primary key (entry_id, tag_id)
+        );
+        create view entry_tag_names as
+            select entry_tags.rowid as position, entry_tags.entry_id as entry_id, tags.name as value
+            from entry_tags join tags on tags.id = entry_tags.tag_id;
+    ")?;
+
+    for (id, tags) in oldTags {
+        for tag in tags.split(" ").filter(|t| !t.is_empty()) {
+            conn.execute("insert or ignore into tags (name) values (?1)", &[tag])?;
+            conn.execute("insert or ignore into entry_tags (entry_id, tag_id) select ?1, id from tags where name = ?2",
+                         params![id, tag])?;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Custom fields are declared in the config file, only their values are stored
+fn createEntryFields(conn: &Connection) -> Result<()> {
+    conn.execute_batch("
+        create table entry_fields (
+            entry_id integer not null references reading_entries(id) on delete cascade,
+            name text not null,
+            value text not null,
+            primary key (entry_id, name)
+        );
+    ")?;
+
+    return Ok(());
+}
+
+/// Highlights and notes, e.g. imported from a Kindle. The same clipping is
+/// stored only once per entry, so imports can be repeated.
+fn createClippings(conn: &Connection) -> Result<()> {
+    conn.execute_batch("
+        create table clippings (
+            id integer primary key autoincrement,
+            entry_id integer not null references reading_entries(id) on delete cascade,
+            kind text not null,
+            location text not null default '',
+            page text not null default '',
+            added_at timestamp,
+            content text not null,
+            unique (entry_id, kind, location, content)
+        );
+    ")?;
+
+    return Ok(());
+}
+
+/// Stores the statuses `keepUnknownStatuses` put aside in the field
+/// `legacy_status`. There are none unless the statuses were migrated in the same run.
+fn moveLegacyStatuses(conn: &Connection) -> Result<()> {
+    conn.execute("insert into entry_fields (entry_id, name, value) select entry_id, 'legacy_status', status from temp.legacy_statuses", NO_PARAMS)?;
+    return Ok(());
+}
+
+/// Creates a database with the schema used before versioning was introduced
+#[cfg(test)]
+fn createUnversionedDb(name: &str) -> (std::path::PathBuf, Connection) {
+    let path = std::env::temp_dir().join(format!("rdnglst-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch("
+        create table reading_entries (
+            id integer primary key,
+            title text not null default '',
+            author text not null default '',
+            genre text not null default '',
+            format text not null default '',
+            tags text not null default '',
+            status text not null default '',
+            created_at timestamp default current_timestamp,
+            updated_at timestamp default current_timestamp
+        );
+        insert into reading_entries (status, tags) values
+            ('Read', 'myth usa'), ('done ', ''), ('currently reading', 'humor  myth'), ('', ' '), ('someday', 'usa'), ('Paused', '');
+    ").unwrap();
+
+    return (path, conn);
+}
+
+#[test]
+fn test_free_text_statuses_are_migrated() {
+    use crate::{Backend, Status};
+
+    let (path, conn) = createUnversionedDb("status");
+    drop(conn);
+
+    let backend = crate::SqliteBackend::new(path.to_str().unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let entries = backend.getAllEntries().unwrap();
+    let statuses: Vec<Status> = entries.iter().map(|re| re.status).collect();
+    assert_eq!(statuses, vec![Status::Finished, Status::Finished, Status::Reading, Status::Wishlist, Status::Wishlist, Status::Paused]);
+
+    let legacy: Vec<Option<&String>> = entries.iter().map(|re| re.fields.get("legacy_status")).collect();
+    let someday = String::from("someday");
+    assert_eq!(legacy, vec![None, None, None, None, Some(&someday), None], "Unknown statuses are kept");
+}
+
+#[test]
+fn test_space_separated_tags_are_migrated() {
+    use crate::Backend;
+
+    let (path, conn) = createUnversionedDb("tags");
+    drop(conn);
+
+    let backend = crate::SqliteBackend::new(path.to_str().unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let tags: Vec<Vec<String>> = backend.getAllEntries().unwrap().into_iter().map(|re| re.tags).collect();
+    let expected: Vec<Vec<&str>> = vec![vec!["myth", "usa"], vec![], vec!["humor", "myth"], vec![], vec!["usa"], vec![]];
+    assert_eq!(tags, expected);
+}
+
+#[test]
+fn test_migrations_are_applied_once_and_dry_runs_change_nothing() {
+    let (path, conn) = createUnversionedDb("migrate");
+
+    let versions = |applied: Vec<(&Migration, Vec<String>)>| -> Vec<u32> { applied.iter().map(|(m, _)| m.version).collect() };
+
+    let dryRun = migrate(&conn, true).unwrap();
+    assert_eq!(dryRun[1].1, vec![String::from("Item 5 '': the unknown status 'someday' became wishlist, it is kept in the field legacy_status")]);
+    assert!(dryRun.iter().filter(|(m, _)| m.version != 2).all(|(_, notes)| notes.is_empty()));
+    assert_eq!(versions(dryRun), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(schemaVersion(&conn).unwrap(), 0);
+    let status: String = conn.query_row("select status from reading_entries where id = 1", NO_PARAMS, |row| row.get(0)).unwrap();
+    assert_eq!(status, "Read");
+
+    assert_eq!(versions(migrate(&conn, false).unwrap()), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(schemaVersion(&conn).unwrap(), latestVersion());
+    assert!(migrate(&conn, false).unwrap().is_empty());
+
+    conn.execute_batch(&format!("pragma user_version = {}", latestVersion() + 1)).unwrap();
+    assert!(migrate(&conn, false).is_err(), "Databases from newer versions must not be touched");
+
+    drop(conn);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,314 @@
+use readinglist::{Error, ReadingEntry, Result, formatEnumToString, statusEnumToString};
+use readinglist::fields::{FieldDefinition, FieldType};
+
+use chrono::NaiveDateTime;
+
+use crate::{columnTitle, columnValue};
+
+/// How the list is printed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    // the box drawn table of comfy_table
+    Table,
+    Json,
+    // one JSON object per line
+    Jsonl,
+    Csv,
+    Tsv,
+    Yaml,
+    Markdown,
+    // aligned columns without any lines
+    Plain
+}
+
+pub const OUTPUT_FORMATS: [&str; 8] = ["table", "json", "jsonl", "csv", "tsv", "yaml", "markdown", "plain"];
+
+/// Timestamps are written in UTC, which is what the backend keeps
+pub const MACHINE_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+pub fn stringToOutputFormat(name: &str) -> Result<OutputFormat> {
+    match name.trim().to_lowercase().as_str() {
+        "table" => return Ok(OutputFormat::Table),
+        "json" => return Ok(OutputFormat::Json),
+        "jsonl" => return Ok(OutputFormat::Jsonl),
+        "csv" => return Ok(OutputFormat::Csv),
+        "tsv" => return Ok(OutputFormat::Tsv),
+        "yaml" => return Ok(OutputFormat::Yaml),
+        "markdown" => return Ok(OutputFormat::Markdown),
+        "plain" => return Ok(OutputFormat::Plain),
+        _ => return Err(Error::Validation(format!("Unknown output format '{}'. Use one of {}", name, OUTPUT_FORMATS.join(", "))))
+    }
+}
+
+/// A value of the records the machine readable formats are made of
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    // in a form JSON accepts as is
+    Number(String),
+    Text(String),
+    List(Vec<Value>),
+    Record(Vec<(String, Value)>)
+}
+
+/// The names of the columns of `entryToRecord` as flat as CSV needs them.
+/// Custom fields follow the builtin ones in the order they are declared.
+pub fn recordColumns(fields: &Vec<FieldDefinition>) -> Vec<String> {
+    let mut columns: Vec<String> = vec!["id", "title", "author", "genre", "format", "status", "tags", "created", "updated", "started", "finished"]
+        .iter().map(|x| x.to_string()).collect();
+    columns.extend(fields.iter().map(|def| def.name.clone()));
+
+    return columns;
+}
+
+/// Every field of the entry in the order of `recordColumns`. Custom fields
+/// are gathered under `fields`, every declared one is present.
+pub fn entryToRecord(re: &ReadingEntry, fields: &Vec<FieldDefinition>) -> Vec<(String, Value)> {
+    let timestamp = |x: &Option<NaiveDateTime>| match x {
+        Some(x) => Value::Text(x.format(MACHINE_TIMESTAMP_FORMAT).to_string()),
+        None => Value::Null
+    };
+
+    let mut custom: Vec<(String, Value)> = Vec::new();
+    for def in fields {
+        let value = match (re.fields.get(&def.name), &def.t) {
+            (None, _) => Value::Null,
+            (Some(x), _) if x.is_empty() => Value::Null,
+            (Some(x), FieldType::Number) => Value::Number(x.clone()),
+            (Some(x), FieldType::Bool) => Value::Bool(x == "true"),
+            (Some(x), _) => Value::Text(x.clone())
+        };
+        custom.push((def.name.clone(), value));
+    }
+
+    return vec![
+        (String::from("id"), Value::Number(re.id.to_string())),
+        (String::from("title"), Value::Text(re.title.clone())),
+        (String::from("author"), Value::Text(re.author.clone())),
+        (String::from("genre"), Value::Text(re.genre.clone())),
+        (String::from("format"), Value::Text(formatEnumToString(&re.format))),
+        (String::from("status"), Value::Text(statusEnumToString(&re.status))),
+        (String::from("tags"), Value::List(re.tags.iter().map(|t| Value::Text(t.clone())).collect())),
+        (String::from("created"), timestamp(&re.created_at)),
+        (String::from("updated"), timestamp(&re.updated_at)),
+        (String::from("started"), timestamp(&re.started_at)),
+        (String::from("finished"), timestamp(&re.finished_at)),
+        (String::from("fields"), Value::Record(custom))
+    ];
+}
+
+pub fn jsonString(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+
+    return quoted;
+}
+
+pub fn toJson(value: &Value) -> String {
+    match value {
+        Value::Null => return String::from("null"),
+        Value::Bool(x) => return x.to_string(),
+        Value::Number(x) => return x.clone(),
+        Value::Text(x) => return jsonString(x),
+        Value::List(xs) => return format!("[{}]", xs.iter().map(|x| toJson(x)).collect::<Vec<String>>().join(", ")),
+        Value::Record(pairs) => {
+            let members: Vec<String> = pairs.iter().map(|(k, v)| format!("{}: {}", jsonString(k), toJson(v))).collect();
+            return format!("{{{}}}", members.join(", "));
+        }
+    }
+}
+
+/// A YAML scalar or flow sequence. Strings are double quoted, which YAML
+/// reads like JSON strings.
+fn toYamlScalar(value: &Value) -> String {
+    match value {
+        Value::Null => return String::from("null"),
+        Value::Record(pairs) if pairs.is_empty() => return String::from("{}"),
+        _ => return toJson(value)
+    }
+}
+
+fn toYaml(records: &Vec<Vec<(String, Value)>>) -> String {
+    if records.is_empty() {
+        return String::from("[]\n");
+    }
+
+    let mut yaml = String::new();
+    for record in records {
+        let mut prefix = "- ";
+        for (key, value) in record {
+            match value {
+                Value::Record(pairs) if !pairs.is_empty() => {
+                    yaml.push_str(&format!("{}{}:\n", prefix, key));
+                    for (k, v) in pairs {
+                        yaml.push_str(&format!("    {}: {}\n", k, toYamlScalar(v)));
+                    }
+                },
+                _ => yaml.push_str(&format!("{}{}: {}\n", prefix, key, toYamlScalar(value)))
+            }
+            prefix = "  ";
+        }
+    }
+
+    return yaml;
+}
+
+/// The value as a single cell of CSV or TSV. Lists are joined with commas,
+/// which the backend rejects in tags.
+fn toCell(value: &Value) -> String {
+    match value {
+        Value::Null => return String::new(),
+        Value::Bool(x) => return x.to_string(),
+        Value::Number(x) | Value::Text(x) => return x.clone(),
+        Value::List(xs) => return xs.iter().map(|x| toCell(x)).collect::<Vec<String>>().join(","),
+        Value::Record(_) => return String::new()
+    }
+}
+
+/// The record with the custom fields lifted to the top, see `recordColumns`
+fn flatten(record: &Vec<(String, Value)>) -> Vec<Value> {
+    let mut values = Vec::new();
+    for (_, value) in record {
+        match value {
+            Value::Record(pairs) => values.extend(pairs.iter().map(|(_, v)| v.clone())),
+            _ => values.push(value.clone())
+        }
+    }
+
+    return values;
+}
+
+fn csvCell(s: &str) -> String {
+    if s.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        return format!("\"{}\"", s.replace("\"", "\"\""));
+    }
+    return s.to_string();
+}
+
+/// TSV can't quote, so tabs, line breaks and backslashes are escaped with a backslash
+fn tsvCell(s: &str) -> String {
+    return s.replace("\\", "\\\\").replace("\t", "\\t").replace("\n", "\\n").replace("\r", "\\r");
+}
+
+fn markdownCell(s: &str) -> String {
+    return s.replace("|", "\\|").replace("\n", " ");
+}
+
+/// Renders the entries in any format but `Table`. Machine readable formats
+/// contain every field, Markdown and plain text only `columns`.
+pub fn render(entries: &Vec<ReadingEntry>, format: OutputFormat, fields: &Vec<FieldDefinition>, columns: &Vec<String>, dateFormat: &str) -> String {
+    let records: Vec<Vec<(String, Value)>> = entries.iter().map(|re| entryToRecord(re, fields)).collect();
+
+    let lines: Vec<String> = match format {
+        OutputFormat::Json => {
+            if records.is_empty() {
+                vec![String::from("[]")]
+            } else {
+                let objects: Vec<String> = records.iter().map(|r| format!("  {}", toJson(&Value::Record(r.clone())))).collect();
+                vec![String::from("["), objects.join(",\n"), String::from("]")]
+            }
+        },
+        OutputFormat::Jsonl => records.iter().map(|r| toJson(&Value::Record(r.clone()))).collect(),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let cell = if format == OutputFormat::Csv { csvCell } else { tsvCell };
+            let separator = if format == OutputFormat::Csv { "," } else { "\t" };
+
+            let mut lines = vec![recordColumns(fields).iter().map(|x| cell(x)).collect::<Vec<String>>().join(separator)];
+            for record in &records {
+                lines.push(flatten(record).iter().map(|v| cell(&toCell(v))).collect::<Vec<String>>().join(separator));
+            }
+            lines
+        },
+        OutputFormat::Yaml => return toYaml(&records),
+        OutputFormat::Markdown => {
+            let mut lines = vec![
+                format!("| {} |", columns.iter().map(|c| markdownCell(&columnTitle(c))).collect::<Vec<String>>().join(" | ")),
+                format!("|{}|", columns.iter().map(|_| "---").collect::<Vec<&str>>().join("|"))
+            ];
+            for re in entries {
+                lines.push(format!("| {} |", columns.iter().map(|c| markdownCell(&columnValue(re, c, dateFormat))).collect::<Vec<String>>().join(" | ")));
+            }
+            lines
+        },
+        OutputFormat::Plain => {
+            let mut rows: Vec<Vec<String>> = vec![columns.iter().map(|c| columnTitle(c)).collect()];
+            rows.extend(entries.iter().map(|re| columns.iter().map(|c| columnValue(re, c, dateFormat).replace("\n", " ")).collect()));
+
+            let widths: Vec<usize> = (0..columns.len()).map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0)).collect();
+            rows.iter()
+                .map(|r| {
+                    let cells: Vec<String> = r.iter().enumerate().map(|(i, x)| format!("{:width$}", x, width = widths[i])).collect();
+                    return cells.join("  ").trim_end().to_string();
+                })
+                .collect()
+        },
+        OutputFormat::Table => Vec::new()
+    };
+
+    return lines.iter().map(|line| format!("{}\n", line)).collect();
+}
+
+#[test]
+fn test_every_format_has_a_stable_schema() {
+    use readinglist::{Format, Status};
+    use std::collections::BTreeMap;
+
+    let fields = vec![
+        FieldDefinition::new("pages", FieldType::Number, None).unwrap(),
+        FieldDefinition::new("signed", FieldType::Bool, None).unwrap(),
+        FieldDefinition::new("note", FieldType::Text, None).unwrap()
+    ];
+    let mut values = BTreeMap::new();
+    values.insert(String::from("pages"), String::from("412"));
+    values.insert(String::from("signed"), String::from("false"));
+    let entries = vec![ReadingEntry {
+        id: 2,
+        title: String::from("Good Omens, \"the\" novel"),
+        author: String::from("Neil Gaiman|Terry Pratchett"),
+        genre: String::from("fan\ttasy"),
+        format: Format::Kindle,
+        status: Status::Reading,
+        tags: vec![String::from("humor"), String::from("myth")],
+        created_at: Some(chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(12, 0, 0).unwrap()),
+        updated_at: None,
+        started_at: None,
+        finished_at: None,
+        fields: values
+    }];
+    let columns = vec![String::from("title"), String::from("author")];
+    let out = |format: OutputFormat| render(&entries, format, &fields, &columns, "%Y-%m-%d");
+
+    assert_eq!(out(OutputFormat::Jsonl),
+               "{\"id\": 2, \"title\": \"Good Omens, \\\"the\\\" novel\", \"author\": \"Neil Gaiman|Terry Pratchett\", \"genre\": \"fan\\ttasy\", \
+                \"format\": \"kindle\", \"status\": \"reading\", \"tags\": [\"humor\", \"myth\"], \"created\": \"2024-01-02T12:00:00Z\", \
+                \"updated\": null, \"started\": null, \"finished\": null, \"fields\": {\"pages\": 412, \"signed\": false, \"note\": null}}\n");
+    assert!(out(OutputFormat::Json).starts_with("[\n  {\"id\": 2, "));
+    assert_eq!(render(&Vec::new(), OutputFormat::Json, &fields, &columns, ""), "[]\n");
+
+    assert_eq!(out(OutputFormat::Csv),
+               "id,title,author,genre,format,status,tags,created,updated,started,finished,pages,signed,note\n\
+                2,\"Good Omens, \"\"the\"\" novel\",Neil Gaiman|Terry Pratchett,fan\ttasy,kindle,reading,\"humor,myth\",2024-01-02T12:00:00Z,,,,412,false,\n");
+    assert_eq!(out(OutputFormat::Tsv).lines().nth(1).unwrap(),
+               "2\tGood Omens, \"the\" novel\tNeil Gaiman|Terry Pratchett\tfan\\ttasy\tkindle\treading\thumor,myth\t2024-01-02T12:00:00Z\t\t\t\t412\tfalse\t");
+
+    let yaml = out(OutputFormat::Yaml);
+    assert!(yaml.starts_with("- id: 2\n  title: \"Good Omens, \\\"the\\\" novel\"\n"), "{}", yaml);
+    assert!(yaml.ends_with("  updated: null\n  started: null\n  finished: null\n  fields:\n    pages: 412\n    signed: false\n    note: null\n"), "{}", yaml);
+
+    assert_eq!(out(OutputFormat::Markdown), "| Title | Author |\n|---|---|\n| Good Omens, \"the\" novel | Neil Gaiman\\|Terry Pratchett |\n");
+    assert_eq!(out(OutputFormat::Plain), "Title                    Author\nGood Omens, \"the\" novel  Neil Gaiman|Terry Pratchett\n");
+
+    assert!(stringToOutputFormat("xml").is_err());
+}
@@ -1,34 +1,99 @@
 use super::CONFIG;
 use crate::log::debug;
 
-use std::result;
+use std::fmt;
 use std::collections::HashMap;
 
-// Nachdem man die Query geparst hat, kann man mit dem gelieferten Modifier die 
+use regex::{Regex, RegexBuilder};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+
+use readinglist::{Page, SortDirection, SortKey, SqlFilter};
+use readinglist::fields::FieldType;
+
+// Nachdem man die Query geparst hat, kann man mit dem gelieferten Ausdruck die 
 // einzelnen Zeilen einer Tabelle abgrasse;
-pub fn eval(modifier: &Modifier, row: &mut TableRow) -> bool {
-    match &modifier.right {
-        Some(x) => {
+pub fn eval(query: &Query, row: &mut TableRow) -> bool {
+    match &query.filter {
+        Some(expr) => return eval_expr(expr, row),
+        None => return true
+    }
+}
+
+fn eval_expr(expr: &Expr, row: &mut TableRow) -> bool {
+    match expr {
+        Expr::Comparison(comp) => return perform_comparison(comp, row),
+        Expr::Modifier(modifier) => {
             match modifier.t {
-                ModType::And => return perform_comparison(&modifier.left, row) && perform_comparison(&x, row),
-                ModType::Or => {
-                    debug(&"Modifier or not covered yet".to_string());
-                    return false;
-                }
+                ModType::And => return eval_expr(&modifier.left, row) && eval_expr(&modifier.right, row),
+                ModType::Or => return eval_expr(&modifier.left, row) || eval_expr(&modifier.right, row)
             }
         },
-        None => return perform_comparison(&modifier.left, row)
+        Expr::Not(inner) => return !eval_expr(inner, row)
     }
 }
 
 fn perform_comparison(comp: &Comparison, row: &mut TableRow) -> bool {
-    match &comp.t {
-        Equal => {
-            let col_value = row.get(&comp.col.name);
+    let col_value = match row.get(&comp.col.name) {
+        Some(x) => x,
+        None => {
+            debug(&format!("Could not find column {} in row", &comp.col.name));
+            return false;
+        }
+    };
+
+    debug(&format!("Performing {:?} comparison [{:?} against {}]", comp.t, &col_value, &comp.ident.name));
+
+    if comp.t == CompType::DateRange {
+        match col_value {
+            Value::Date(x) => return comp.since.map_or(true, |since| x >= since) && comp.until.map_or(true, |until| x < until),
+            _ => return false
+        }
+    }
+
+    // A text is treated as a set holding a single element
+    let elements = match col_value {
+        Value::Text(x) => vec![x],
+        Value::List(x) => x,
+        Value::Date(x) => vec![x.format(TIMESTAMP_FORMAT).to_string()]
+    };
+
+    match comp.t {
+        CompType::Empty => return elements.iter().all(|e| e.is_empty()),
+        CompType::HasAll => return comp.values.iter().all(|v| elements.iter().any(|e| same_value(comp, e, v))),
+        CompType::HasAny => return comp.values.iter().any(|v| elements.iter().any(|e| same_value(comp, e, v))),
+        _ => return elements.iter().any(|e| compare_text(comp, e))
+    }
+}
+
+fn same_value(comp: &Comparison, element: &String, value: &String) -> bool {
+    if comp.ignore_case {
+        return element.to_lowercase() == value.to_lowercase();
+    }
+    return element == value;
+}
+
+fn compare_text(comp: &Comparison, col_value: &String) -> bool {
+    if comp.t == CompType::Matches {
+        match &comp.pattern {
+            Some(p) => return p.is_match(col_value),
+            None => return false
+        }
+    }
 
-            debug(&format!("Performin equal comparison [{} == {}]", &comp.ident.name, &col_value));
+    let (col_value, ident) = if comp.ignore_case {
+        (col_value.to_lowercase(), comp.ident.name.to_lowercase())
+    } else {
+        (col_value.clone(), comp.ident.name.clone())
+    };
 
-            return comp.ident.name == col_value;
+    match comp.t {
+        CompType::Equal => return col_value == ident,
+        CompType::Contains => return col_value.contains(&ident),
+        CompType::StartsWith => return col_value.starts_with(&ident),
+        CompType::EndsWith => return col_value.ends_with(&ident),
+        _ => {
+            debug(&format!("Comparison {:?} not covered yet", comp.t));
+            return false;
         }
     }
 }
@@ -38,7 +103,8 @@ fn equal_comparison_works() {
     let comp = Comparison {
         t: CompType:: Equal,
         col: Column {name: String::from("foo")},
-        ident: Ident { name: String::from("bar") }
+        ident: Ident { name: String::from("bar") },
+        ..Comparison::default()
     };
 
     let mut row = TableRow::new();
@@ -51,9 +117,370 @@ fn equal_comparison_works() {
     assert!(!perform_comparison(&comp, &mut row), "Comparison should be false");
 }
 
+#[test]
+fn substring_comparisons_work() {
+    let cols = vec![String::from("author")];
+    let mut row = TableRow::new();
+    row.insert(&String::from("author"), &String::from("Neil Gaiman"));
+
+    let matching = vec![
+        "author contains Gaiman",
+        "author contains \"l G\"",
+        "author startswith Neil",
+        "author endswith man",
+        "author matches /^Neil\\s+G/",
+        "author matches /gaiman$/i",
+    ];
+    for q in matching {
+        assert!(eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should match", q);
+    }
+
+    let not_matching = vec![
+        "author is Gaiman",
+        "author contains gaiman",
+        "author startswith Gaiman",
+        "author endswith Neil",
+        "author matches /^Gaiman/",
+    ];
+    for q in not_matching {
+        assert!(!eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should not match", q);
+    }
+}
+
+#[test]
+fn ignore_case_applies_to_all_comparators() {
+    let cols = vec![String::from("author")];
+    let mut row = TableRow::new();
+    row.insert(&String::from("author"), &String::from("Neil Gaiman"));
+
+    for q in vec!["author is \"neil gaiman\"", "author contains GAIMAN", "author startswith neil", "author endswith MAN", "author matches /^neil/"] {
+        assert!(!eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should be case sensitive", q);
+        assert!(eval(&parse_query(q, &cols, true).unwrap(), &mut row), "{} should ignore case", q);
+    }
+}
+
+#[test]
+fn tags_have_set_semantics() {
+    let cols = vec![String::from("tags"), String::from("genre")];
+    let mut row = TableRow::new();
+    row.insert_list(&String::from("tags"), &vec![String::from("rust"), String::from("async"), String::from("web dev")]);
+    row.insert(&String::from("genre"), &String::from(""));
+
+    let matching = vec![
+        "tags has rust",
+        "tags has \"web dev\"",
+        "tags has all (rust, async)",
+        "tags has any (ml, stats, async)",
+        "tags contains as",
+        "genre is empty",
+        "not tags is empty",
+    ];
+    for q in matching {
+        assert!(eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should match", q);
+    }
+
+    let not_matching = vec![
+        "tags has web",
+        "tags has Rust",
+        "tags has all (rust, ml)",
+        "tags has any (ml, stats)",
+        "tags is empty",
+    ];
+    for q in not_matching {
+        assert!(!eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should not match", q);
+    }
+
+    row.insert_list(&String::from("tags"), &Vec::new());
+    assert!(eval(&parse_query("tags is empty", &cols, false).unwrap(), &mut row));
+    assert!(!eval(&parse_query("tags has any (rust)", &cols, false).unwrap(), &mut row));
+}
+
+/// Describes how a query column is stored in the database
+pub enum SqlColumn {
+    Text(String),
+
+    // A multivalued column stored as one row per element in `view`, which
+    // has the columns entry_id and value. `column` is the name under which
+    // the elements are selected with an entry.
+    Linked { column: String, view: String },
+
+    // A nullable timestamp stored as text in TIMESTAMP_FORMAT
+    Timestamp(String),
+
+    // A custom field stored in the table entry_fields
+    Field { name: String, t: FieldType }
+}
+
+impl SqlColumn {
+    /// The name to sort by, see `Page`
+    pub fn name(&self) -> String {
+        match self {
+            SqlColumn::Text(x) => return x.clone(),
+            SqlColumn::Linked { column, .. } => return column.clone(),
+            SqlColumn::Timestamp(x) => return x.clone(),
+            SqlColumn::Field { name, t: FieldType::Number } => return format!("field:{}:number", name),
+            SqlColumn::Field { name, .. } => return format!("field:{}", name)
+        }
+    }
+}
+
+/// Translates the order of a page to the names of the columns in the
+/// database
+pub fn to_sql_page(page: &Page, columns: &HashMap<String, SqlColumn>) -> Page {
+    let mut sql_page = page.clone();
+    for key in sql_page.order.iter_mut() {
+        if let Some(col) = columns.get(&key.column) {
+            key.column = col.name();
+        }
+    }
+    return sql_page;
+}
+
+/// Translates the filter of a query into an SQL filter that selects exactly
+/// the rows `eval` would accept.
+///
+/// Returns `None` if any part of the filter can not be expressed in SQL, e.g.
+/// regular expressions or columns missing from `columns`. The query then has
+/// to be evaluated in memory.
+pub fn to_sql(query: &Query, columns: &HashMap<String, SqlColumn>) -> Option<SqlFilter> {
+    let expr = match &query.filter {
+        Some(x) => x,
+        None => return Some(SqlFilter::all())
+    };
+
+    let mut params = Vec::new();
+    let clause = expr_to_sql(expr, columns, &mut params)?;
+    return Some(SqlFilter { clause: clause, params: params });
+}
+
+fn expr_to_sql(expr: &Expr, columns: &HashMap<String, SqlColumn>, params: &mut Vec<String>) -> Option<String> {
+    match expr {
+        Expr::Comparison(comp) => return comparison_to_sql(comp, columns, params),
+        Expr::Modifier(modifier) => {
+            let left = expr_to_sql(&modifier.left, columns, params)?;
+            let right = expr_to_sql(&modifier.right, columns, params)?;
+            match modifier.t {
+                ModType::And => return Some(format!("({} and {})", left, right)),
+                ModType::Or => return Some(format!("({} or {})", left, right))
+            }
+        },
+        Expr::Not(inner) => return Some(format!("(not {})", expr_to_sql(inner, columns, params)?))
+    }
+}
+
+fn comparison_to_sql(comp: &Comparison, columns: &HashMap<String, SqlColumn>, params: &mut Vec<String>) -> Option<String> {
+    let fold = |x: &str| -> String {
+        if comp.ignore_case {
+            return format!("casefold({})", x);
+        }
+        return x.to_string();
+    };
+
+    match columns.get(&comp.col.name)? {
+        SqlColumn::Text(col) => return text_comparison_to_sql(comp, col, params),
+        SqlColumn::Linked { view, .. } => {
+            let elements = format!("select 1 from {} where entry_id = reading_entries.id", view);
+            let mut element_terms = |values: &Vec<String>| -> Vec<String> {
+                let mut terms = Vec::new();
+                for v in values {
+                    params.push(v.clone());
+                    terms.push(format!("exists ({} and {} = {})", elements, fold("value"), fold("?")));
+                }
+                return terms;
+            };
+
+            match comp.t {
+                CompType::Equal => return Some(element_terms(&vec![comp.ident.name.clone()]).join("")),
+                CompType::HasAll => return Some(format!("({})", element_terms(&comp.values).join(" and "))),
+                CompType::HasAny => return Some(format!("({})", element_terms(&comp.values).join(" or "))),
+                CompType::Empty => return Some(format!("not exists ({})", elements)),
+                _ => return None
+            }
+        },
+        SqlColumn::Timestamp(col) => return timestamp_comparison_to_sql(comp, col, params),
+        SqlColumn::Field { name, t: FieldType::Date } => {
+            // dates are stored without a time
+            let col = format!("(select datetime(value) from entry_fields where entry_id = reading_entries.id and name = '{}')", name);
+            return timestamp_comparison_to_sql(comp, &col, params);
+        },
+        SqlColumn::Field { name, .. } => {
+            let col = format!("coalesce((select value from entry_fields where entry_id = reading_entries.id and name = '{}'), '')", name);
+            return text_comparison_to_sql(comp, &col, params);
+        }
+    }
+}
+
+/// `col` may be any expression giving a text
+fn text_comparison_to_sql(comp: &Comparison, col: &str, params: &mut Vec<String>) -> Option<String> {
+    let fold = |x: &str| -> String {
+        if comp.ignore_case {
+            return format!("casefold({})", x);
+        }
+        return x.to_string();
+    };
+
+    let col = fold(col);
+    let value = fold("?");
+    match comp.t {
+        CompType::Equal => {
+            params.push(comp.ident.name.clone());
+            return Some(format!("{} = {}", col, value));
+        },
+        CompType::Contains => {
+            params.push(comp.ident.name.clone());
+            return Some(format!("instr({}, {}) > 0", col, value));
+        },
+        CompType::StartsWith => {
+            params.push(comp.ident.name.clone());
+            return Some(format!("instr({}, {}) = 1", col, value));
+        },
+        CompType::EndsWith => {
+            for _ in 0..3 {
+                params.push(comp.ident.name.clone());
+            }
+            // folding may change the length, e.g. of 'İ'
+            return Some(format!("(length({}) = 0 or substr({}, -length({})) = {})", value, col, value, value));
+        },
+        CompType::HasAll | CompType::HasAny => {
+            // a text is a set with a single element
+            let mut terms = Vec::new();
+            for v in &comp.values {
+                params.push(v.clone());
+                terms.push(format!("{} = {}", col, value));
+            }
+            let op = if comp.t == CompType::HasAll { " and " } else { " or " };
+            return Some(format!("({})", terms.join(op)));
+        },
+        CompType::Empty => return Some(format!("{} = ''", col)),
+        _ => return None
+    }
+}
+
+/// `col` may be any expression giving a nullable timestamp in TIMESTAMP_FORMAT
+fn timestamp_comparison_to_sql(comp: &Comparison, col: &str, params: &mut Vec<String>) -> Option<String> {
+    match comp.t {
+        CompType::DateRange => {
+            let mut terms = vec![format!("{} is not null", col)];
+            if let Some(since) = comp.since {
+                params.push(since.format(TIMESTAMP_FORMAT).to_string());
+                terms.push(format!("{} >= ?", col));
+            }
+            if let Some(until) = comp.until {
+                params.push(until.format(TIMESTAMP_FORMAT).to_string());
+                terms.push(format!("{} < ?", col));
+            }
+            return Some(format!("({})", terms.join(" and ")));
+        },
+        CompType::Empty => return Some(format!("{} is null", col)),
+        _ => return None
+    }
+}
+
+#[test]
+fn date_comparisons_work() {
+    let cols = vec![String::from("created")];
+    let mut row = TableRow::new();
+    let created = NaiveDateTime::parse_from_str("2024-01-15 10:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    row.insert_date(&String::from("created"), &created);
+
+    let matching = vec![
+        "created after 2024-01-14",
+        "created after \"2024-01-15 10:29\"",
+        "created before 2024-01-16",
+        "created between 2024-01-15 and 2024-01-15",
+        "created between 2023-12-01 and 2024-02-01",
+        "created startswith 2024-01",
+        "not created is empty",
+    ];
+    for q in matching {
+        assert!(eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should match", q);
+    }
+
+    let not_matching = vec![
+        "created after 2024-01-15",
+        "created before 2024-01-15",
+        "created before \"2024-01-15 10:30:00\"",
+        "created between 2024-01-16 and 2024-02-01",
+        "created within 30d",
+    ];
+    for q in not_matching {
+        assert!(!eval(&parse_query(q, &cols, false).unwrap(), &mut row), "{} should not match", q);
+    }
+
+    let recently = Utc::now().naive_utc() - Duration::days(3);
+    row.insert_date(&String::from("created"), &recently);
+    assert!(eval(&parse_query("created within 1w", &cols, false).unwrap(), &mut row));
+    assert!(!eval(&parse_query("created within 48h", &cols, false).unwrap(), &mut row));
+
+    // entries without a timestamp are neither before nor after anything
+    row.insert(&String::from("created"), &String::from(""));
+    assert!(!eval(&parse_query("created after 2000-01-01", &cols, false).unwrap(), &mut row));
+    assert!(eval(&parse_query("created is empty", &cols, false).unwrap(), &mut row));
+
+    let err = parse_query("created after yesterday", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("yesterday"));
+    assert_eq!(err.expected, String::from("a date (YYYY-MM-DD)"));
+
+    let err = parse_query("created within 30", &cols, false).unwrap_err();
+    assert_eq!(err.expected, String::from("a duration (e.g. 12h, 30d or 2w)"));
+
+    let err = parse_query("created between 2024-01-01 or 2024-02-01", &cols, false).unwrap_err();
+    assert_eq!(err.expected, String::from("'and'"));
+}
+
+#[test]
+fn test_to_sql() {
+    let cols = vec![String::from("author"), String::from("tags")];
+    let mut sql_columns = HashMap::new();
+    sql_columns.insert(String::from("author"), SqlColumn::Text(String::from("author")));
+    sql_columns.insert(String::from("tags"), SqlColumn::Linked { column: String::from("tags"), view: String::from("tag_names") });
+
+    let expr = parse_query("author is Gaiman and not (tags has rust or tags is empty)", &cols, false).unwrap();
+    assert_eq!(to_sql(&expr, &sql_columns), Some(SqlFilter {
+        clause: String::from("(author = ? and (not ((exists (select 1 from tag_names where entry_id = reading_entries.id and value = ?)) or not exists (select 1 from tag_names where entry_id = reading_entries.id))))"),
+        params: vec![String::from("Gaiman"), String::from("rust")]
+    }));
+
+    let expr = parse_query("author contains gaiman", &cols, true).unwrap();
+    assert_eq!(to_sql(&expr, &sql_columns), Some(SqlFilter {
+        clause: String::from("instr(casefold(author), casefold(?)) > 0"),
+        params: vec![String::from("gaiman")]
+    }));
+
+    // regular expressions can only be evaluated in memory
+    let expr = parse_query("author is Gaiman and author matches /G/", &cols, false).unwrap();
+    assert_eq!(to_sql(&expr, &sql_columns), None);
+
+    let expr = parse_query("tags has any (\"web dev\", Rust)", &cols, true).unwrap();
+    assert_eq!(to_sql(&expr, &sql_columns), Some(SqlFilter {
+        clause: String::from("(exists (select 1 from tag_names where entry_id = reading_entries.id and casefold(value) = casefold(?)) or exists (select 1 from tag_names where entry_id = reading_entries.id and casefold(value) = casefold(?)))"),
+        params: vec![String::from("web dev"), String::from("Rust")]
+    }));
+}
+
+/// Words with a meaning of their own between comparisons. They can not be
+/// used as column names.
+pub const KEYWORDS: [&str; 7] = ["and", "or", "not", "order", "by", "limit", "offset"];
+
+/// The format timestamps are stored in. Text comparators compare against
+/// a timestamp in this format.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The value of a single column within a `TableRow`
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+
+    // Multivalued columns like tags. Compared as a set.
+    List(Vec<String>),
+
+    // A point in time in UTC
+    Date(NaiveDateTime)
+}
+
 // Abstraction over a table row holding columns and associated values
 pub struct TableRow {
-    values: HashMap<String, String>
+    values: HashMap<String, Value>
 }
 
 impl TableRow {
@@ -61,15 +488,20 @@ impl TableRow {
         return TableRow { values: HashMap::new() }
     }
 
-    pub fn get(&mut self, column: &String) -> String {
-        match self.values.get(column) {
-            Some(x) => return x.clone(),
-            None => panic!("Could not find column {} in row", column)
-        }
+    pub fn get(&mut self, column: &String) -> Option<Value> {
+        return self.values.get(column).cloned();
     }
 
     pub fn insert(&mut self, column: &String, value: &String) {
-        self.values.insert(column.clone(), value.clone());
+        self.values.insert(column.clone(), Value::Text(value.clone()));
+    }
+
+    pub fn insert_list(&mut self, column: &String, values: &Vec<String>) {
+        self.values.insert(column.clone(), Value::List(values.clone()));
+    }
+
+    pub fn insert_date(&mut self, column: &String, value: &NaiveDateTime) {
+        self.values.insert(column.clone(), Value::Date(*value));
     }
 }
 
@@ -77,164 +509,781 @@ pub struct Table {
     rows: Vec<TableRow>
 }
 
-fn is_col(cols: &Vec<String>, token: &String) -> bool {
-    return cols.iter().any(|x| x == token);
+/// A single lexical unit of a query. Quoted values keep their spaces and are
+/// never mistaken for keywords.
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    // pattern and flags of a /regex/flags literal
+    Regex(String, String),
+    LParen,
+    RParen,
+    Comma
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Word(x) => return x.clone(),
+            Token::Quoted(x) => return format!("\"{}\"", x),
+            Token::Regex(p, flags) => return format!("/{}/{}", p, flags),
+            Token::LParen => return String::from("("),
+            Token::RParen => return String::from(")"),
+            Token::Comma => return String::from(",")
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self {
+            Token::Word(x) => return x == keyword,
+            _ => return false
+        }
+    }
 }
 
-fn is_mod(mods: &Vec<String>, token: &String) -> bool {
-    return mods.iter().any(|x| x == token);
+/// Error produced when a query can not be parsed. Carries the offending
+/// token, its byte offset within the query and a hint about what the
+/// parser expected instead.
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    /// The offending token as it appeared in the query. Empty if the query
+    /// ended prematurely.
+    pub token: String,
+    pub offset: usize,
+    pub expected: String
 }
 
-#[derive(PartialEq, Debug)]
-enum State {
-    Start,
-    Col,
-    Value,
-    CompOp,
-    MultiWordValue,
-    Operator
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.is_empty() {
+            return write!(f, "expected {} but reached the end of the query", self.expected);
+        }
+        return write!(f, "expected {} but found '{}'", self.expected, self.token);
+    }
+}
+
+/// Splits a query into words and double quoted values. Every token is paired
+/// with its byte offset in the query.
+fn tokenize(q: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = q.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if c == '"' {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, x)) => value.push(x),
+                    None => return Err(QueryError {
+                        token: format!("\"{}", value),
+                        offset: offset,
+                        expected: String::from("a closing '\"'")
+                    })
+                }
+            }
+            debug(&format!("Found quoted value '{}'", value));
+            tokens.push((offset, Token::Quoted(value)));
+        } else if c == '/' {
+            let mut pattern = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '/')) => break,
+                    Some((_, '\\')) => {
+                        // keep escapes, but an escaped slash does not end the pattern
+                        pattern.push('\\');
+                        if let Some((_, x)) = chars.next() {
+                            pattern.push(x);
+                        }
+                    },
+                    Some((_, x)) => pattern.push(x),
+                    None => return Err(QueryError {
+                        token: format!("/{}", pattern),
+                        offset: offset,
+                        expected: String::from("a closing '/'")
+                    })
+                }
+            }
+            let mut flags = String::new();
+            while let Some(&(_, x)) = chars.peek() {
+                if !x.is_alphabetic() {
+                    break;
+                }
+                flags.push(x);
+                chars.next();
+            }
+            debug(&format!("Found regex /{}/{}", pattern, flags));
+            tokens.push((offset, Token::Regex(pattern, flags)));
+        } else if c == '(' {
+            tokens.push((offset, Token::LParen));
+        } else if c == ')' {
+            tokens.push((offset, Token::RParen));
+        } else if c == ',' {
+            tokens.push((offset, Token::Comma));
+        } else {
+            let mut word = String::new();
+            word.push(c);
+            while let Some(&(_, x)) = chars.peek() {
+                if x.is_whitespace() || x == '"' || x == '(' || x == ')' || x == ',' {
+                    break;
+                }
+                word.push(x);
+                chars.next();
+            }
+            tokens.push((offset, Token::Word(word)));
+        }
+    }
+
+    return Ok(tokens);
+}
+
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize("author is \"Neil Gaiman\"  and status is read").unwrap();
+    assert_eq!(tokens, vec![
+        (0, Token::Word(String::from("author"))),
+        (7, Token::Word(String::from("is"))),
+        (10, Token::Quoted(String::from("Neil Gaiman"))),
+        (25, Token::Word(String::from("and"))),
+        (29, Token::Word(String::from("status"))),
+        (36, Token::Word(String::from("is"))),
+        (39, Token::Word(String::from("read"))),
+    ]);
+}
+
+/// Recursive descent parser over the tokens of a query.
+///
+/// Grammar:
+///
+/// ```text
+/// query      := or_expr
+/// or_expr    := and_expr ("or" and_expr)*
+/// and_expr   := not_expr ("and" not_expr)*
+/// not_expr   := "not" not_expr | primary
+/// primary    := "(" or_expr ")" | comparison
+/// comparison := column comparator value
+///             | column "is" "empty"
+///             | column "has" ("all" | "any") "(" value ("," value)* ")"
+///             | column ("after" | "before") date
+///             | column "between" date "and" date
+///             | column "within" duration
+/// comparator := "is" | "has" | "contains" | "startswith" | "endswith" | "matches"
+/// ```
+///
+/// Dates are written as `YYYY-MM-DD`, optionally followed by a time as in
+/// `"YYYY-MM-DD HH:MM"`, and denote the whole day (or minute). `between`
+/// includes both ends. Durations are a number followed by `h`, `d` or `w`,
+/// e.g. `30d`. All times are UTC.
+///
+/// `matches` expects a regular expression written as `/pattern/`, optionally
+/// followed by the `i` flag to ignore case. Multivalued columns match a
+/// comparator if any of their elements does.
+///
+/// `not` binds tighter than `and`, which binds tighter than `or`. The filter
+/// may be followed by clauses controlling order and paging of the result:
+///
+/// ```text
+/// full_query := [query] ["order" "by" sort_key ("," sort_key)*]
+///               ["limit" number] ["offset" number]
+/// sort_key   := column ["asc" | "desc"]
+/// ```
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+    columns: &'a Vec<String>,
+    ignore_case: bool,
+
+    // the point in time `within` counts back from
+    now: NaiveDateTime
+}
+
+impl<'a> Parser<'a> {
+    fn new(q: &str, columns: &'a Vec<String>, ignore_case: bool) -> Result<Parser<'a>, QueryError> {
+        return Ok(Parser {
+            tokens: tokenize(q)?,
+            pos: 0,
+            end: q.len(),
+            columns: columns,
+            ignore_case: ignore_case,
+            now: Utc::now().naive_utc()
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.pos).map(|(_, t)| t);
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        return token;
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(t) => return t.is_keyword(keyword),
+            None => return false
+        }
+    }
+
+    /// Builds an error pointing at the current token, or at the end of the
+    /// query if all tokens have been consumed.
+    fn unexpected(&self, expected: &str) -> QueryError {
+        match self.tokens.get(self.pos) {
+            Some((offset, t)) => return QueryError {
+                token: t.text(),
+                offset: *offset,
+                expected: expected.to_string()
+            },
+            None => return QueryError {
+                token: String::new(),
+                offset: self.end,
+                expected: expected.to_string()
+            }
+        }
+    }
+
+    fn at_clause(&self) -> bool {
+        return self.peek().is_none() || self.at_keyword("order") || self.at_keyword("limit") || self.at_keyword("offset");
+    }
+
+    fn parse(&mut self) -> Result<Query, QueryError> {
+        let filter = if self.at_clause() {
+            None
+        } else {
+            Some(self.parse_or()?)
+        };
+
+        let mut page = Page::default();
+
+        if self.at_keyword("order") {
+            self.next();
+            if !self.at_keyword("by") {
+                return Err(self.unexpected("'by'"));
+            }
+            self.next();
+
+            page.order.push(self.parse_sort_key()?);
+            while self.peek() == Some(&Token::Comma) {
+                self.next();
+                page.order.push(self.parse_sort_key()?);
+            }
+        }
+
+        if self.at_keyword("limit") {
+            self.next();
+            page.limit = Some(self.parse_number()?);
+        }
+
+        if self.at_keyword("offset") {
+            self.next();
+            page.offset = Some(self.parse_number()?);
+        }
+
+        if self.peek().is_some() {
+            if filter.is_some() && page.order.is_empty() && page.limit.is_none() && page.offset.is_none() {
+                return Err(self.unexpected("an operator ('and' or 'or')"));
+            }
+            return Err(self.unexpected("the end of the query"));
+        }
+
+        return Ok(Query { filter: filter, page: page });
+    }
+
+    fn parse_sort_key(&mut self) -> Result<SortKey, QueryError> {
+        let column = match self.peek() {
+            Some(Token::Word(x)) if is_col(self.columns, x) => x.clone(),
+            _ => return Err(self.unexpected(&format!("a column ({})", self.columns.join(", "))))
+        };
+        self.next();
+
+        let direction = if self.at_keyword("desc") {
+            self.next();
+            SortDirection::Desc
+        } else {
+            if self.at_keyword("asc") {
+                self.next();
+            }
+            SortDirection::Asc
+        };
+
+        return Ok(SortKey { column: column, direction: direction });
+    }
+
+    fn parse_number(&mut self) -> Result<u32, QueryError> {
+        let number = match self.peek() {
+            Some(Token::Word(x)) => x.parse::<u32>().ok(),
+            _ => None
+        };
+
+        match number {
+            Some(x) => {
+                self.next();
+                return Ok(x);
+            },
+            None => return Err(self.unexpected("a positive number"))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+
+        while self.at_keyword("or") {
+            self.next();
+            let right = self.parse_and()?;
+            debug(&format!("Found alternative: {:?}", right));
+            left = Expr::Modifier(Modifier::new(left, right, ModType::Or));
+        }
+
+        return Ok(left);
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_not()?;
+
+        while self.at_keyword("and") {
+            self.next();
+            let right = self.parse_not()?;
+            debug(&format!("Found additional comparison: {:?}", right));
+            left = Expr::Modifier(Modifier::new(left, right, ModType::And));
+        }
+
+        return Ok(left);
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        if self.at_keyword("not") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+
+        return self.parse_primary();
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        if self.peek() != Some(&Token::LParen) {
+            return self.parse_comparison();
+        }
+
+        self.next();
+        let expr = self.parse_or()?;
+        if self.peek() != Some(&Token::RParen) {
+            return Err(self.unexpected("')'"));
+        }
+        self.next();
+
+        return Ok(expr);
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, QueryError> {
+        let col = match self.peek() {
+            Some(Token::Word(x)) if is_col(self.columns, x) => x.clone(),
+            _ => return Err(self.unexpected(&format!("a column ({})", self.columns.join(", "))))
+        };
+        self.next();
+        debug(&format!("Found column {}", col));
+
+        let t = match self.peek() {
+            Some(Token::Word(x)) if x == "is" => CompType::Equal,
+            Some(Token::Word(x)) if x == "has" => CompType::HasAny,
+            Some(Token::Word(x)) if x == "contains" => CompType::Contains,
+            Some(Token::Word(x)) if x == "startswith" => CompType::StartsWith,
+            Some(Token::Word(x)) if x == "endswith" => CompType::EndsWith,
+            Some(Token::Word(x)) if x == "matches" => CompType::Matches,
+            Some(Token::Word(x)) if x == "after" || x == "before" || x == "between" || x == "within" => CompType::DateRange,
+            _ => return Err(self.unexpected("a comparison operator ('is', 'has', 'contains', 'startswith', 'endswith', 'matches', 'after', 'before', 'between' or 'within')"))
+        };
+
+        if t == CompType::DateRange {
+            return self.parse_date_range(col);
+        }
+        self.next();
+
+        if t == CompType::Matches {
+            return self.parse_regex(col);
+        }
+
+        if t == CompType::HasAny {
+            return self.parse_has(col);
+        }
+
+        if t == CompType::Equal && self.at_keyword("empty") {
+            self.next();
+            return Ok(Expr::Comparison(Comparison {
+                t: CompType::Empty,
+                col: Column { name: col },
+                ..Comparison::default()
+            }));
+        }
+
+        let value = self.parse_value()?;
+        debug(&format!("Found value '{}'", value));
+
+        return Ok(Expr::Comparison(Comparison {
+            t: t,
+            col: Column { name: col },
+            ident: Ident { name: value },
+            ignore_case: self.ignore_case,
+            ..Comparison::default()
+        }));
+    }
+
+    fn parse_value(&mut self) -> Result<String, QueryError> {
+        let value = match self.peek() {
+            Some(Token::Word(x)) => x.clone(),
+            Some(Token::Quoted(x)) => x.clone(),
+            _ => return Err(self.unexpected("a value"))
+        };
+        self.next();
+        return Ok(value);
+    }
+
+    fn parse_has(&mut self, col: String) -> Result<Expr, QueryError> {
+        let is_list = self.tokens.get(self.pos + 1).map(|(_, t)| t) == Some(&Token::LParen);
+
+        let t = if is_list && self.at_keyword("all") {
+            CompType::HasAll
+        } else if is_list && self.at_keyword("any") {
+            CompType::HasAny
+        } else {
+            let value = self.parse_value()?;
+            return Ok(Expr::Comparison(Comparison {
+                t: CompType::HasAny,
+                col: Column { name: col },
+                ident: Ident { name: value.clone() },
+                ignore_case: self.ignore_case,
+                values: vec![value],
+                ..Comparison::default()
+            }));
+        };
+        self.next();
+        self.next();
+
+        let mut values = vec![self.parse_value()?];
+        while self.peek() == Some(&Token::Comma) {
+            self.next();
+            values.push(self.parse_value()?);
+        }
+
+        if self.peek() != Some(&Token::RParen) {
+            return Err(self.unexpected("',' or ')'"));
+        }
+        self.next();
+        debug(&format!("Found values {:?}", values));
+
+        return Ok(Expr::Comparison(Comparison {
+            t: t,
+            col: Column { name: col },
+            ident: Ident { name: values.join(", ") },
+            ignore_case: self.ignore_case,
+            values: values,
+            ..Comparison::default()
+        }));
+    }
+
+    fn parse_date_range(&mut self, col: String) -> Result<Expr, QueryError> {
+        let comparator = self.next().map(|t| t.text()).unwrap_or_default();
+
+        let (since, until) = match comparator.as_str() {
+            "after" => (Some(self.parse_date()?.1), None),
+            "before" => (None, Some(self.parse_date()?.0)),
+            "between" => {
+                let (since, _) = self.parse_date()?;
+                if !self.at_keyword("and") {
+                    return Err(self.unexpected("'and'"));
+                }
+                self.next();
+                let (_, until) = self.parse_date()?;
+                (Some(since), Some(until))
+            },
+            _ => (Some(self.now - self.parse_duration()?), None)
+        };
+        debug(&format!("Found date range [{:?}, {:?})", since, until));
+
+        return Ok(Expr::Comparison(Comparison {
+            t: CompType::DateRange,
+            col: Column { name: col },
+            ident: Ident { name: comparator },
+            since: since,
+            until: until,
+            ..Comparison::default()
+        }));
+    }
+
+    /// Parses a date and returns the range of time it denotes
+    fn parse_date(&mut self) -> Result<(NaiveDateTime, NaiveDateTime), QueryError> {
+        let text = match self.peek() {
+            Some(Token::Word(x)) => x.clone(),
+            Some(Token::Quoted(x)) => x.clone(),
+            _ => String::new()
+        };
+
+        let range = if let Ok(x) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S") {
+            (x, x + Duration::seconds(1))
+        } else if let Ok(x) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M") {
+            (x, x + Duration::minutes(1))
+        } else if let Ok(x) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+            let start = x.and_hms_opt(0, 0, 0).unwrap();
+            (start, start + Duration::days(1))
+        } else {
+            return Err(self.unexpected("a date (YYYY-MM-DD)"));
+        };
+        self.next();
+
+        return Ok(range);
+    }
+
+    fn parse_duration(&mut self) -> Result<Duration, QueryError> {
+        let text = match self.peek() {
+            Some(Token::Word(x)) => x.clone(),
+            _ => String::new()
+        };
+
+        let split = text.len().saturating_sub(1);
+        let amount = text.get(..split).and_then(|x| x.parse::<i64>().ok());
+        let duration = match (amount, text.get(split..)) {
+            (Some(x), Some("h")) if x >= 0 => Duration::hours(x),
+            (Some(x), Some("d")) if x >= 0 => Duration::days(x),
+            (Some(x), Some("w")) if x >= 0 => Duration::weeks(x),
+            _ => return Err(self.unexpected("a duration (e.g. 12h, 30d or 2w)"))
+        };
+        self.next();
+
+        return Ok(duration);
+    }
+
+    fn parse_regex(&mut self, col: String) -> Result<Expr, QueryError> {
+        let (pattern, flags) = match self.peek() {
+            Some(Token::Regex(p, flags)) => (p.clone(), flags.clone()),
+            _ => return Err(self.unexpected("a regular expression (/pattern/)"))
+        };
+
+        let ignore_case = match flags.as_str() {
+            "" => self.ignore_case,
+            "i" => true,
+            _ => return Err(self.unexpected("a regular expression flag ('i')"))
+        };
+
+        let compiled = match RegexBuilder::new(&pattern).case_insensitive(ignore_case).build() {
+            Ok(x) => x,
+            Err(e) => return Err(self.unexpected(&format!("a valid regular expression ({})", e)))
+        };
+        self.next();
+        debug(&format!("Found pattern '{}'", pattern));
+
+        return Ok(Expr::Comparison(Comparison {
+            t: CompType::Matches,
+            col: Column { name: col },
+            ident: Ident { name: pattern },
+            ignore_case: ignore_case,
+            pattern: Some(compiled),
+            ..Comparison::default()
+        }));
+    }
+}
+
+fn is_col(cols: &Vec<String>, token: &String) -> bool {
+    return cols.iter().any(|x| x == token);
+}
+
+#[cfg(test)]
+fn comparison_of(expr: &Expr) -> &Comparison {
+    match expr {
+        Expr::Comparison(c) => return c,
+        _ => panic!("Expected a comparison, got {:?}", expr)
+    }
 }
 
 #[test]
 fn test_query_parsing_single_value() {
     let cols = vec![String::from("eins")];
 
-    let modi = parse_query("eins is zwei", &cols);
-    assert_eq!(modi.t, ModType::And);
-    assert_eq!(modi.left.col.name, String::from("eins"));
-    assert_eq!(modi.left.ident.name, String::from("zwei"));
-    assert!(modi.right.is_none());
-
+    let query = parse_query("eins is zwei", &cols, false).unwrap();
+    let comp = comparison_of(query.filter.as_ref().unwrap());
+    assert_eq!(comp.col.name, String::from("eins"));
+    assert_eq!(comp.ident.name, String::from("zwei"));
 }
 
 #[test]
 fn test_query_parsing_multivalue() {
     let cols = vec![String::from("eins")];
 
-    let modi = parse_query("eins is \"zwei drei\"", &cols);
-    assert_eq!(modi.t, ModType::And);
-    assert_eq!(modi.left.col.name, String::from("eins"));
-    assert_eq!(modi.left.ident.name, String::from("zwei drei"));
-    assert_eq!(modi.left.t, CompType::Equal);
-    assert!(modi.right.is_none());
+    let query = parse_query("eins is \"zwei drei\"", &cols, false).unwrap();
+    let comp = comparison_of(query.filter.as_ref().unwrap());
+    assert_eq!(comp.col.name, String::from("eins"));
+    assert_eq!(comp.ident.name, String::from("zwei drei"));
+    assert_eq!(comp.t, CompType::Equal);
 }
 
-/// Parses a query string building the syntax tree. Performs
-/// checks against valid columns by using the passed vec of 
-/// column names.
-///
-/// Returns the root node of the tree which can then be used
-/// to evaluate the query against real data
-pub fn parse_query(q: &str, columns: &Vec<String>) -> Modifier {
-    let mut comp: Comparison = Comparison::default();
-    let mut root: Modifier = Modifier::default();
-
-    let mut token = String::new();
-
-    let mut current_state = State::Start;
-
-    let mut currentModifier: &mut Modifier = &mut root;
-    let mut current_comp: &mut Comparison = &mut currentModifier.left;
-
-    let mods: Vec<String> = vec!["is".to_string(), "has".to_string()];
-
-    for c in q.chars() {
-        // end of word
-        //
-        if c != ' ' {
-            if c == '"' {
-                if current_state == State::Value {
-                    // start of multiword value
-                    debug(&"Start of multiwordvalue".to_string());
-                    current_state = State::MultiWordValue;
-                } 
-                else {
-                    // end of multiwordvalue
-                    // is this the last char in the query?
-                    // If so, should we be fine b
-                    //
-                    // if not, we have to set the new state
-                    debug(&"End of multiwordvalue".to_string());
-                    current_state = State::MultiWordValue;
-                }
-            } else {
-                token.push(c);
+#[test]
+fn test_query_parsing_chained_and() {
+    let cols = vec![String::from("a"), String::from("b"), String::from("c")];
+
+    // and is left associative: ((a and b) and c)
+    let query = parse_query("a is x and b is \"y z\" and c is w", &cols, false).unwrap();
+    match query.filter {
+        Some(Expr::Modifier(ref m)) => {
+            assert_eq!(m.t, ModType::And);
+            assert_eq!(comparison_of(&m.right).col.name, String::from("c"));
+            match *m.left {
+                Expr::Modifier(ref inner) => {
+                    assert_eq!(comparison_of(&inner.left).col.name, String::from("a"));
+                    assert_eq!(comparison_of(&inner.right).ident.name, String::from("y z"));
+                },
+                _ => panic!("Expected nested modifier")
             }
-        } 
-        else {
-            //  we encountered a space which acts as a separator
-
-            if current_state == State::MultiWordValue  {
-                debug(&format!("Detected multiword value {}", token));
-                //  add the space if we are dealing with a multiword value
-                token.push(c);
-            } 
-            else if current_state == State::Start {
-                // We expect a column name at the very beginning
-                if !is_col(&columns, &token) {
-                    debug(&format!("Expected a col name. Got {}", token));
-                    panic!(String::from("Expected a col ident"));
-                }
+        },
+        _ => panic!("Expected a modifier")
+    }
 
-                debug(&format!("Found column {}", token));
-                current_comp.col = Column {name: token};
+    let mut row = TableRow::new();
+    row.insert(&String::from("a"), &String::from("x"));
+    row.insert(&String::from("b"), &String::from("y z"));
+    row.insert(&String::from("c"), &String::from("w"));
+    assert!(eval(&query, &mut row));
 
-                // Comparison Operator should be next
-                current_state = State::CompOp;
+    row.insert(&String::from("c"), &String::from("nope"));
+    assert!(!eval(&query, &mut row), "The last term must not be dropped");
+}
 
-                token = String::new();
+#[test]
+fn test_order_limit_and_offset() {
+    let cols = vec![String::from("author"), String::from("title")];
 
-            } else if current_state == State::CompOp {
-                if token == "is" {
-                    current_comp.t = CompType::Equal;
-                    debug(&"Found comp type equal".to_string());
-                    current_state = State::Value;
-                } else {
-                    panic!("Expected a comparison operator ('is' or 'has') but found {}", token);
-                }
-                token = String::new();
-            } else if current_state == State::Value {
-                debug(&format!("Found value '{}'", token));
-                current_comp.ident = Ident {name: token};
-                current_state = State::Operator;
-                token = String::new();
-            } else if current_state == State::Operator {
-                // an operator creates new comparison
-                
-
-                if is_mod(&mods, &token) == false {
-                    panic!("expected an operator ('and' or 'or'), Found {}", &token);
-                }
+    let query = parse_query("author is Gaiman order by author asc, title desc limit 20 offset 40", &cols, false).unwrap();
+    assert!(query.filter.is_some());
+    assert_eq!(query.page, Page {
+        order: vec![
+            SortKey { column: String::from("author"), direction: SortDirection::Asc },
+            SortKey { column: String::from("title"), direction: SortDirection::Desc }
+        ],
+        limit: Some(20),
+        offset: Some(40)
+    });
 
-                let new_comp = Comparison {
-                    t: comp.t, 
-                    col: Column { name: current_comp.col.name.clone() }, 
-                    ident: Ident{ name: String::from("") },
-                };
+    let query = parse_query("order by title", &cols, false).unwrap();
+    assert!(query.filter.is_none());
+    assert_eq!(query.page.order, vec![SortKey { column: String::from("title"), direction: SortDirection::Asc }]);
 
-                currentModifier.right = Some(new_comp);
-                match currentModifier.right {
-                    Some(ref mut x) => current_comp = x,
-                    None => panic!("Could not get right hand side of modifier")
-                }
-                debug(&format!("Found additional comparison: {:?}", current_comp));
-                current_state = State::Value;
-                token = String::new();
-            }
+    let query = parse_query("offset 5", &cols, false).unwrap();
+    assert_eq!(query.page, Page { order: Vec::new(), limit: None, offset: Some(5) });
 
-            continue;
-        } 
+    let query = parse_query("", &cols, false).unwrap();
+    assert!(query.filter.is_none());
+    assert_eq!(query.page, Page::default());
 
+    let err = parse_query("order by rating", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("rating"));
+
+    let err = parse_query("limit -3", &cols, false).unwrap_err();
+    assert_eq!(err.expected, String::from("a positive number"));
+
+    let err = parse_query("limit 3 order by title", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("order"));
+    assert_eq!(err.expected, String::from("the end of the query"));
+}
+
+#[cfg(test)]
+fn row_of(values: &[(&str, &str)]) -> TableRow {
+    let mut row = TableRow::new();
+    for (col, value) in values {
+        row.insert(&col.to_string(), &value.to_string());
     }
-    if current_state != State::Value && current_state != State::MultiWordValue {
-        debug(&format!("We are at the end of the query and expecting a value. But we are currently in {:?}", current_state));
-        panic!(String::from("Wrong state"));
-    }
+    return row;
+}
+
+#[test]
+fn test_or_not_and_grouping() {
+    let cols = vec![String::from("author"), String::from("status"), String::from("genre")];
+    let mut gaiman_unread = row_of(&[("author", "Gaiman"), ("status", "unread"), ("genre", "fantasy")]);
+    let mut pratchett_read = row_of(&[("author", "Pratchett"), ("status", "read"), ("genre", "fantasy")]);
+    let mut taleb_unread = row_of(&[("author", "Taleb"), ("status", "unread"), ("genre", "essay")]);
+
+    let q = parse_query("status is read or status is reading", &cols, false).unwrap();
+    assert!(eval(&q, &mut pratchett_read));
+    assert!(!eval(&q, &mut gaiman_unread));
+
+    let q = parse_query("not genre is fantasy", &cols, false).unwrap();
+    assert!(!eval(&q, &mut gaiman_unread));
+    assert!(eval(&q, &mut taleb_unread));
 
-    debug(&format!("Found final ident {}", token));
-    current_comp.ident = Ident {name: token};
+    let q = parse_query("(author is Gaiman or author is Pratchett) and status is unread", &cols, false).unwrap();
+    assert!(eval(&q, &mut gaiman_unread));
+    assert!(!eval(&q, &mut pratchett_read));
+    assert!(!eval(&q, &mut taleb_unread));
 
-    return root;
+    // and binds tighter than or
+    let q = parse_query("author is Taleb or author is Gaiman and status is read", &cols, false).unwrap();
+    assert!(eval(&q, &mut taleb_unread));
+    assert!(!eval(&q, &mut gaiman_unread));
+
+    let q = parse_query("not (genre is fantasy or status is read)", &cols, false).unwrap();
+    assert!(eval(&q, &mut taleb_unread));
+    assert!(!eval(&q, &mut gaiman_unread));
+}
+
+#[test]
+fn test_unbalanced_parens_are_rejected() {
+    let cols = vec![String::from("author")];
+    let err = parse_query("(author is Gaiman", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from(""));
+    assert_eq!(err.offset, 17);
+    assert_eq!(err.expected, String::from("')'"));
+}
+
+#[test]
+fn test_query_errors_point_at_offending_token() {
+    let cols = vec![String::from("author"), String::from("status")];
+
+    let err = parse_query("auther is Gaiman", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("auther"));
+    assert_eq!(err.offset, 0);
+    assert_eq!(err.expected, String::from("a column (author, status)"));
+
+    let err = parse_query("author is Gaiman and status iss read", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("iss"));
+    assert_eq!(err.offset, 28);
+
+    let err = parse_query("author is Gaiman status is read", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("status"));
+    assert_eq!(err.expected, String::from("an operator ('and' or 'or')"));
+
+    let err = parse_query("author is", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from(""));
+    assert_eq!(err.offset, 9);
+    assert_eq!(err.expected, String::from("a value"));
+
+    let err = parse_query("author is \"Neil Gaiman", &cols, false).unwrap_err();
+    assert_eq!(err.offset, 10);
+    assert_eq!(err.expected, String::from("a closing '\"'"));
+
+    let err = parse_query("author matches /(/", &cols, false).unwrap_err();
+    assert_eq!(err.token, String::from("/(/"));
+    assert!(err.expected.starts_with("a valid regular expression"));
+
+    let err = parse_query("author matches Gaiman", &cols, false).unwrap_err();
+    assert_eq!(err.expected, String::from("a regular expression (/pattern/)"));
+}
+
+/// Parses a query string building the syntax tree. Performs
+/// checks against valid columns by using the passed vec of 
+/// column names. With `ignore_case` set all comparisons of the
+/// query ignore the case of the compared values.
+///
+/// Returns the query holding the root node of the tree which can
+/// then be used to evaluate the query against real data, or a
+/// `QueryError` describing the first token that does not fit the
+/// grammar
+pub fn parse_query(q: &str, columns: &Vec<String>, ignore_case: bool) -> Result<Query, QueryError> {
+    return Parser::new(q, columns, ignore_case)?.parse();
 }
 
 
@@ -242,6 +1291,13 @@ pub fn parse_query(q: &str, columns: &Vec<String>) -> Modifier {
 enum CompType {
     Equal,
     Contains,
+    StartsWith,
+    EndsWith,
+    Matches,
+    HasAll,
+    HasAny,
+    Empty,
+    DateRange,
     No
 }
 
@@ -251,37 +1307,52 @@ enum ModType {
     Or
 }
 
-pub struct Modifier {
-    left: Comparison,
+/// A parsed query. A query without a filter matches every row.
+#[derive(Debug)]
+pub struct Query {
+    pub filter: Option<Expr>,
+    pub page: Page
+}
 
-    // Comparison gehoert nicht dem Modifier, sondern dem Option
-    // Kann ich mir den Wert also vom Option leihen???
-    right: Option<Comparison>,
-    t: ModType
+/// A node of the syntax tree produced by `parse_query`
+#[derive(Debug)]
+pub enum Expr {
+    Comparison(Comparison),
+    Modifier(Modifier),
+    Not(Box<Expr>)
 }
 
-impl Default for Modifier {
-    fn default() -> Modifier {
-        return Modifier {
-            left: Comparison::default(),
-            right: None,
-            t: ModType::And
-        }
-    }
+/// Combines two subexpressions with a logical operator
+#[derive(Debug)]
+pub struct Modifier {
+    left: Box<Expr>,
+    right: Box<Expr>,
+    t: ModType
 }
 
 impl Modifier {
-    fn new(left: Comparison, right: Option<Comparison>, t: ModType) -> Modifier {
-        return Modifier {left: left, right: right, t: t}
+    fn new(left: Expr, right: Expr, t: ModType) -> Modifier {
+        return Modifier {left: Box::new(left), right: Box::new(right), t: t}
     }
 }
 
 /// Defines the comparison of a value with the content of a column
 #[derive(Debug)]
-struct Comparison {
+pub struct Comparison {
     ident: Ident,
     col: Column,
-    t: CompType
+    t: CompType,
+    ignore_case: bool,
+
+    // compiled form of ident for CompType::Matches
+    pattern: Option<Regex>,
+
+    // the values to look for with CompType::HasAll and CompType::HasAny
+    values: Vec<String>,
+
+    // the range [since, until) of CompType::DateRange. Open if None.
+    since: Option<NaiveDateTime>,
+    until: Option<NaiveDateTime>
 }
 
 impl Default for Comparison {
@@ -289,7 +1360,12 @@ impl Default for Comparison {
         return Comparison {
             t: CompType::No,
             col: Column { name: String::from("") },
-            ident: Ident { name: String::from("") }
+            ident: Ident { name: String::from("") },
+            ignore_case: false,
+            pattern: None,
+            values: Vec::new(),
+            since: None,
+            until: None
         }
     }
 }